@@ -1,35 +1,426 @@
-use icalendar::{Calendar, CalendarComponent, Component, Event};
+use chrono::{DateTime, TimeDelta, Utc};
+use icalendar::{Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 
-use crate::util::date_perhaps_time_to_utc;
+use crate::error::{Error, Result};
+use crate::store::{CALENDARS_DIR, CalendarStore};
+use crate::util::{date_perhaps_time_to_utc, is_utc_alias, parse_ical_duration};
 
-pub fn get_current_event(discord_user_id: u64) -> Option<Event> {
-    let calendars = std::fs::read_dir(format!("calendars/{discord_user_id}")).ok()?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("ics"))
-        .collect::<Vec<_>>();
+/// Paths of every calendar file the user has uploaded
+pub fn calendar_paths(discord_user_id: u64) -> Vec<PathBuf> {
+    CalendarStore::with_root(CALENDARS_DIR, discord_user_id).list()
+}
+
+/// Per-calendar settings, stored next to the files in `calendars/<user_id>/`
+const METADATA_FILE: &str = "calendars.json";
+
+/// Serialises read-modify-write cycles on the metadata files
+static METADATA_LOCK: Mutex<()> = Mutex::new(());
+
+/// User settings for one calendar file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarMetadata {
+    /// Disabled calendars are kept on disk but ignored when resolving the status
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Shown instead of the filename
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// When events overlap, the one from the highest priority calendar sets the status
+    #[serde(default)]
+    pub priority: i32,
+    /// Free-form color or label, e.g. `work` or `#ff8800`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub source: CalendarSource,
+}
+
+impl Default for CalendarMetadata {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            display_name: None,
+            priority: 0,
+            label: None,
+            source: CalendarSource::default(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// The user's directory under the shared calendars root
+fn user_dir(discord_user_id: u64) -> PathBuf {
+    CalendarStore::with_root(CALENDARS_DIR, discord_user_id).dir().to_path_buf()
+}
+
+/// Settings for each of the user's calendars, keyed by filename
+///
+/// Calendars without an entry use [`CalendarMetadata::default`].
+pub fn load_calendar_metadata(discord_user_id: u64) -> BTreeMap<String, CalendarMetadata> {
+    read_metadata(&user_dir(discord_user_id))
+}
+
+/// Settings for one calendar file
+pub fn calendar_metadata(discord_user_id: u64, filename: &str) -> CalendarMetadata {
+    load_calendar_metadata(discord_user_id)
+        .remove(filename)
+        .unwrap_or_default()
+}
+
+/// Apply a change to one calendar's settings and persist them
+pub fn update_calendar_metadata<T>(
+    discord_user_id: u64,
+    filename: &str,
+    change: impl FnOnce(&mut CalendarMetadata) -> T,
+) -> Result<T> {
+    update_metadata(&user_dir(discord_user_id), filename, change)
+}
+
+/// The settings kept in a user directory, see [`load_calendar_metadata`]
+pub(crate) fn read_metadata(dir: &Path) -> BTreeMap<String, CalendarMetadata> {
+    read_to_string(dir.join(METADATA_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Like [`update_calendar_metadata`], for the settings kept in `dir`
+pub(crate) fn update_metadata<T>(
+    dir: &Path,
+    filename: &str,
+    change: impl FnOnce(&mut CalendarMetadata) -> T,
+) -> Result<T> {
+    let _guard = METADATA_LOCK.lock().unwrap();
+    let mut metadata = read_metadata(dir);
+    let result = change(metadata.entry(filename.to_string()).or_default());
+    save_metadata(dir, &metadata)?;
+    Ok(result)
+}
+
+/// Forget the settings of a calendar that was removed from `dir`
+pub(crate) fn remove_metadata(dir: &Path, filename: &str) -> Result<()> {
+    let _guard = METADATA_LOCK.lock().unwrap();
+    let mut metadata = read_metadata(dir);
+    if metadata.remove(filename).is_some() {
+        save_metadata(dir, &metadata)?;
+    }
+    Ok(())
+}
+
+fn save_metadata(dir: &Path, metadata: &BTreeMap<String, CalendarMetadata>) -> Result<()> {
+    let metadata_json = serde_json::to_string_pretty(metadata)?;
+    std::fs::write(dir.join(METADATA_FILE), metadata_json)?;
+    Ok(())
+}
+
+/// An event along with the calendar file it came from
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    /// The calendar's display name, or its filename if it has none
+    pub calendar: String,
+    pub event: Event,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl CalendarEvent {
+    pub fn summary(&self) -> &str {
+        self.event.get_summary().unwrap_or_default()
+    }
+
+    /// Whether the event is underway at `now`; it isn't yet at the instant it starts, and no longer
+    /// at the instant it ends
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.start < now && self.end > now
+    }
+
+    /// Whether the event repeats through an `RRULE`; only its first occurrence is ever used
+    pub fn is_recurring(&self) -> bool {
+        self.event.property_value("RRULE").is_some()
+    }
+
+    /// Whether a start or end time is in a named time zone, which is read as UTC rather than converted
+    pub fn has_zoned_times(&self) -> bool {
+        [self.event.get_start(), self.event.get_end()].into_iter().flatten().any(|time| {
+            matches!(time, DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { tzid, .. }) if !is_utc_alias(&tzid))
+        })
+    }
+}
+
+/// The outcome of the last time a calendar file was read
+#[derive(Debug, Clone)]
+pub struct CalendarHealth {
+    pub checked_at: DateTime<Utc>,
+    pub events: usize,
+    /// Events that were skipped, and why
+    pub skipped: Vec<String>,
+    /// Set when the whole file couldn't be read or parsed
+    pub error: Option<String>,
+}
+
+impl CalendarHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none() && self.skipped.is_empty()
+    }
+}
+
+/// Health of every calendar file read since startup, keyed by path
+static HEALTH: LazyLock<Mutex<HashMap<PathBuf, CalendarHealth>>> = LazyLock::new(Default::default);
 
-    for calendar in calendars {
-        let contents = read_to_string(calendar.path()).unwrap();
+/// How the calendar at `path` fared the last time it was read, if it has been read yet
+pub fn calendar_health(path: &Path) -> Option<CalendarHealth> {
+    HEALTH.lock().unwrap().get(path).cloned()
+}
+
+/// A calendar file's usable events, plus the events that had to be skipped
+#[derive(Debug)]
+pub struct ParsedCalendar {
+    /// The calendar's own name, from `X-WR-CALNAME`
+    pub name: Option<String>,
+    pub events: Vec<CalendarEvent>,
+    pub skipped: Vec<Error>,
+}
 
-        let parsed_calendar: Calendar = contents.parse().unwrap();
+/// Read and parse one calendar file, skipping events the status loop can't use
+pub fn read_calendar(path: &Path) -> Result<ParsedCalendar> {
+    let calendar_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
 
-        let now = chrono::Utc::now();
+    let contents = read_to_string(path)?;
+    let parsed_calendar: Calendar = contents.parse().map_err(|message| Error::CalendarParse {
+        calendar: calendar_name.clone(),
+        message,
+    })?;
+
+    let mut parsed = ParsedCalendar {
+        name: parsed_calendar.get_name().map(str::to_string),
+        events: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for component in parsed_calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            continue;
+        };
+
+        match event_times(&event, &calendar_name) {
+            Ok((start, end)) => parsed.events.push(CalendarEvent {
+                calendar: calendar_name.clone(),
+                start,
+                end,
+                event,
+            }),
+            Err(problem) => parsed.skipped.push(problem),
+        }
+    }
+    Ok(parsed)
+}
 
-        for component in &parsed_calendar.components {
-            if let CalendarComponent::Event(event) = component {
-                let start_time = date_perhaps_time_to_utc(&event.get_start().unwrap());
-                let end_time = date_perhaps_time_to_utc(&event.get_end().unwrap());
-                if start_time < now && end_time > now {
-                    println!(
-                        "Event: {}. Starts at: {:?}",
-                        event.get_summary().unwrap(),
-                        start_time
-                    );
-                    return Some(event.clone());
+/// When an event starts and ends, or why the status loop can't use it
+///
+/// Per RFC 5545 an event ends at its DTEND, else after its DURATION, and an all-day event with
+/// neither lasts one day. A timed event with neither takes no time at all, so it is skipped.
+fn event_times(event: &Event, calendar_name: &str) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let missing = |property| Error::MissingProperty {
+        calendar: calendar_name.to_string(),
+        property,
+        summary: event.get_summary().map(str::to_string),
+    };
+    if event.get_summary().is_none() {
+        return Err(missing("SUMMARY"));
+    }
+    let start = event.get_start().ok_or_else(|| missing("DTSTART"))?;
+    let start_utc = date_perhaps_time_to_utc(&start);
+
+    let end = match (event.get_end(), event.property_value("DURATION"), start) {
+        (Some(end), _, _) => date_perhaps_time_to_utc(&end),
+        (None, Some(duration), _) => parse_ical_duration(duration)
+            .and_then(|duration| start_utc.checked_add_signed(duration))
+            .ok_or_else(|| Error::InvalidProperty {
+                calendar: calendar_name.to_string(),
+                property: "DURATION",
+                value: duration.to_string(),
+                summary: event.get_summary().map(str::to_string),
+            })?,
+        (None, None, DatePerhapsTime::Date(_)) => start_utc + TimeDelta::days(1),
+        (None, None, DatePerhapsTime::DateTime(_)) => return Err(missing("DTEND or DURATION")),
+    };
+    Ok((start_utc, end))
+}
+
+/// Every usable event in the user's enabled calendars, highest priority calendar first
+///
+/// Bad files and events are skipped and recorded in [`calendar_health`] as of `now` rather than failing the lot.
+pub fn load_events(discord_user_id: u64, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+    let metadata = load_calendar_metadata(discord_user_id);
+    let mut calendars = Vec::new();
+
+    for path in calendar_paths(discord_user_id) {
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let settings = metadata.get(&filename).cloned().unwrap_or_default();
+        if !settings.enabled {
+            continue;
+        }
+
+        let health = match read_calendar(&path) {
+            Ok(mut parsed) => {
+                for skipped in &parsed.skipped {
+                    eprintln!("Skipping event: {skipped}");
+                }
+                let health = CalendarHealth {
+                    checked_at: now,
+                    events: parsed.events.len(),
+                    skipped: parsed.skipped.iter().map(ToString::to_string).collect(),
+                    error: None,
+                };
+                if let Some(display_name) = &settings.display_name {
+                    for event in &mut parsed.events {
+                        event.calendar = display_name.clone();
+                    }
+                }
+                calendars.push((settings.priority, parsed.events));
+                health
+            }
+            Err(e) => {
+                eprintln!("Skipping calendar {}: {e}", path.display());
+                CalendarHealth {
+                    checked_at: now,
+                    events: 0,
+                    skipped: Vec::new(),
+                    error: Some(e.to_string()),
                 }
             }
+        };
+        HEALTH.lock().unwrap().insert(path, health);
+    }
+
+    // Stable, so calendars of equal priority keep their directory order
+    calendars.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+    calendars.into_iter().flat_map(|(_, events)| events).collect()
+}
+
+/// Where a calendar file came from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalendarSource {
+    /// Uploaded through `/upload_calendar`
+    #[default]
+    Upload,
+}
+
+impl std::fmt::Display for CalendarSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalendarSource::Upload => write!(f, "upload"),
         }
     }
-    None
+}
+
+/// Statistics about one calendar file, for `/calendar_info`
+#[derive(Debug, Clone)]
+pub struct CalendarInfo {
+    pub file: String,
+    pub name: Option<String>,
+    pub metadata: CalendarMetadata,
+    pub events: usize,
+    /// Events carrying an `RRULE`
+    pub recurring: usize,
+    /// Events with times in a named time zone, which are read as UTC
+    pub zoned: usize,
+    /// Earliest start and latest end across all events
+    pub range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    pub modified: Option<DateTime<Utc>>,
+    /// When the status loop last read the file
+    pub last_read: Option<DateTime<Utc>>,
+    pub skipped: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Read one calendar file and summarise it, without failing on a bad file
+pub fn read_calendar_info(path: &Path, metadata: CalendarMetadata) -> CalendarInfo {
+    let mut info = CalendarInfo {
+        file: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        name: None,
+        metadata,
+        events: 0,
+        recurring: 0,
+        zoned: 0,
+        range: None,
+        modified: path.metadata().and_then(|metadata| metadata.modified()).ok().map(DateTime::from),
+        last_read: calendar_health(path).map(|health| health.checked_at),
+        skipped: Vec::new(),
+        error: None,
+    };
+
+    match read_calendar(path) {
+        Ok(parsed) => {
+            info.name = parsed.name;
+            info.events = parsed.events.len();
+            info.recurring = parsed
+                .events
+                .iter()
+                .filter(|event| event.is_recurring())
+                .count();
+            info.zoned = parsed.events.iter().filter(|event| event.has_zoned_times()).count();
+            let first = parsed.events.iter().map(|event| event.start).min();
+            let last = parsed.events.iter().map(|event| event.end).max();
+            info.range = first.zip(last);
+            info.skipped = parsed.skipped.iter().map(ToString::to_string).collect();
+        }
+        Err(e) => info.error = Some(e.to_string()),
+    }
+    info
+}
+
+/// Every event happening at `now`, in calendar order
+pub fn get_current_events(discord_user_id: u64, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+    load_events(discord_user_id, now)
+        .into_iter()
+        .filter(|event| event.is_active_at(now))
+        .collect()
+}
+
+/// Every event overlapping `from..to`, sorted by start time, reading the calendars at `now`
+pub fn get_events_between(
+    discord_user_id: u64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<CalendarEvent> {
+    let mut events: Vec<_> = load_events(discord_user_id, now)
+        .into_iter()
+        .filter(|event| event.start < to && event.end > from)
+        .collect();
+    events.sort_by_key(|event| event.start);
+    events
+}
+
+pub fn get_current_event(discord_user_id: u64, now: DateTime<Utc>) -> Option<CalendarEvent> {
+    let current = get_current_events(discord_user_id, now).into_iter().next()?;
+    println!("Event: {}. Starts at: {:?}", current.summary(), current.start);
+    Some(current)
+}
+
+/// Start time of the earliest event that hasn't begun by `now`, across all of the user's calendars
+pub fn get_next_event_start(discord_user_id: u64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    load_events(discord_user_id, now)
+        .into_iter()
+        .map(|event| event.start)
+        .filter(|start| *start > now)
+        .min()
 }
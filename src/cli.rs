@@ -0,0 +1,376 @@
+use crate::calendar::calendar_paths;
+use crate::clock::SystemClock;
+use crate::config::{add_mapping, load_config, update_default_mapping};
+use crate::connection::{event_to_discord_status, refresh_status_now};
+use crate::lint::{LintReport, lint_calendar};
+use crate::report::status_report;
+use crate::simulate::simulate;
+use crate::sink::build_sinks;
+use crate::util::parse_datetime;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub const USAGE: &str = "\
+Usage:
+  calendar2discord                                  Start the Discord bot and status loop
+  calendar2discord run                              Run only the status loop, without the bot
+  calendar2discord check [--user <id>]              Validate the config and calendars
+  calendar2discord lint <file.ics>...               Report problems in calendar files
+  calendar2discord simulate <from> <to> [--user <id>]
+                                                    Print every status transition between two times
+  calendar2discord status [--user <id>]             Show the current events and resolved status
+  calendar2discord set-default [--message <text>] [--emoji <emoji>]
+                                                    Update the default status
+  calendar2discord add-mapping <event> [--message <text>] [--emoji <emoji>]
+                                                    Add or update an event mapping
+
+Times are RFC 3339 or local `YYYY-MM-DD` / `YYYY-MM-DDTHH:MM`.";
+
+#[derive(Debug)]
+pub enum Command {
+    Bot,
+    Run,
+    Check {
+        user_id: Option<u64>,
+    },
+    Lint {
+        files: Vec<String>,
+    },
+    Simulate {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        user_id: Option<u64>,
+    },
+    Status {
+        user_id: Option<u64>,
+    },
+    SetDefault {
+        message: Option<String>,
+        emoji: Option<String>,
+    },
+    AddMapping {
+        event: String,
+        message: Option<String>,
+        emoji: Option<String>,
+    },
+}
+
+fn parse_time(input: Option<String>, name: &str) -> Result<DateTime<Utc>, String> {
+    let input = input.ok_or_else(|| format!("Missing <{name}>"))?;
+    parse_datetime(&input).ok_or_else(|| format!("Invalid <{name}>: {input}"))
+}
+
+/// Collect `--name value` pairs from the remaining arguments, rejecting anything not in `allowed`
+fn parse_options(mut args: impl Iterator<Item = String>, allowed: &[&str]) -> Result<HashMap<String, String>, String> {
+    let mut options = HashMap::new();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--").filter(|name| allowed.contains(name)) else {
+            return Err(format!("Unexpected argument: {arg}"));
+        };
+        let value = args.next().ok_or_else(|| format!("Missing value for --{name}"))?;
+        options.insert(name.to_string(), value);
+    }
+    Ok(options)
+}
+
+fn parse_user(args: impl Iterator<Item = String>) -> Result<Option<u64>, String> {
+    parse_options(args, &["user"])?
+        .remove("user")
+        .map(|id| id.parse().map_err(|_| format!("Invalid user id: {id}")))
+        .transpose()
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let Some(command) = args.next() else {
+        return Ok(Command::Bot);
+    };
+
+    match command.as_str() {
+        "run" => {
+            parse_options(args, &[])?;
+            Ok(Command::Run)
+        }
+        "check" => Ok(Command::Check {
+            user_id: parse_user(args)?,
+        }),
+        "lint" => {
+            let files: Vec<_> = args.collect();
+            if files.is_empty() {
+                return Err("Missing <file.ics>".to_string());
+            }
+            Ok(Command::Lint { files })
+        }
+        "simulate" => {
+            let from = parse_time(args.next(), "from")?;
+            let to = parse_time(args.next(), "to")?;
+            if to <= from {
+                return Err("<to> must be after <from>".to_string());
+            }
+            Ok(Command::Simulate {
+                from,
+                to,
+                user_id: parse_user(args)?,
+            })
+        }
+        "status" => Ok(Command::Status {
+            user_id: parse_user(args)?,
+        }),
+        "set-default" => {
+            let mut options = parse_options(args, &["message", "emoji"])?;
+            if options.is_empty() {
+                return Err("Provide at least one of --message or --emoji".to_string());
+            }
+            Ok(Command::SetDefault {
+                message: options.remove("message"),
+                emoji: options.remove("emoji"),
+            })
+        }
+        "add-mapping" => {
+            let event = args.next().ok_or("Missing <event>")?;
+            let mut options = parse_options(args, &["message", "emoji"])?;
+            Ok(Command::AddMapping {
+                event,
+                message: options.remove("message"),
+                emoji: options.remove("emoji"),
+            })
+        }
+        other => Err(format!("Unknown command: {other}")),
+    }
+}
+
+/// The user to act on, falling back to the one in the config
+fn resolve_user(user_id: Option<u64>) -> Result<u64, Error> {
+    match user_id {
+        Some(user_id) => Ok(user_id),
+        None => Ok(load_config()?.discord.user_id),
+    }
+}
+
+/// Run the calendar-to-status loop in the foreground, with no bot attached
+pub async fn run_headless() -> Result<(), Error> {
+    let config = load_config()?;
+    println!("Running headless status loop for user {}", config.discord.user_id);
+    event_to_discord_status(
+        config.discord.user_id,
+        true,
+        Arc::new(AtomicBool::new(false)),
+        build_sinks(&config),
+        Arc::new(SystemClock),
+    )
+    .await;
+    Ok(())
+}
+
+/// Lint one file and print the outcome, returning the report
+fn lint_file(path: &Path, indent: &str) -> Result<LintReport, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let report = lint_calendar(&contents);
+
+    println!(
+        "{indent}{}: {} events, {} errors, {} warnings",
+        path.display(),
+        report.events,
+        report.errors(),
+        report.warnings()
+    );
+    for issue in &report.issues {
+        println!("{indent}  {issue}");
+    }
+    Ok(report)
+}
+
+/// Validate the config and the user's calendars
+pub fn run_check(user_id: Option<u64>) -> Result<(), Error> {
+    let config = load_config()?;
+    println!(
+        "Config: OK ({} mappings, {} sinks)",
+        config.mappings.mapping.len(),
+        build_sinks(&config).len()
+    );
+
+    let user_id = user_id.unwrap_or(config.discord.user_id);
+    let paths = calendar_paths(user_id);
+    println!("Calendars for user {user_id}: {}", paths.len());
+
+    let mut errors = 0;
+    for path in paths {
+        errors += lint_file(&path, "  ")?.errors();
+    }
+
+    if errors > 0 {
+        return Err(format!("{errors} calendar errors found").into());
+    }
+    Ok(())
+}
+
+pub fn run_lint(files: &[String]) -> Result<(), Error> {
+    let mut errors = 0;
+    for file in files {
+        errors += lint_file(Path::new(file), "")?.errors();
+    }
+
+    if errors > 0 {
+        return Err(format!("{errors} errors found").into());
+    }
+    Ok(())
+}
+
+pub fn run_simulate(from: DateTime<Utc>, to: DateTime<Utc>, user_id: Option<u64>) -> Result<(), Error> {
+    let user_id = resolve_user(user_id)?;
+    let simulation = simulate(user_id, from, to, chrono::Utc::now());
+
+    for transition in simulation.transitions {
+        println!(
+            "{}  {:<30}  {} {}",
+            transition.at.with_timezone(&Local).format("%Y-%m-%d %H:%M %:z"),
+            match (&transition.event, &transition.calendar) {
+                (Some(event), Some(calendar)) => format!("{event} ({calendar})"),
+                _ => "(no event)".to_string(),
+            },
+            transition.status.emoji,
+            transition.status.message
+        );
+    }
+
+    if simulation.recurring > 0 {
+        println!(
+            "\nNote: {} recurring event(s) were simulated from their first occurrence only, later repeats are not expanded.",
+            simulation.recurring
+        );
+    }
+    if simulation.zoned > 0 {
+        println!(
+            "Note: {} event(s) use a TZID time zone, their times were read as UTC rather than converted.",
+            simulation.zoned
+        );
+    }
+    Ok(())
+}
+
+pub fn run_status(user_id: Option<u64>) -> Result<(), Error> {
+    let report = status_report(resolve_user(user_id)?, chrono::Utc::now());
+    let local = |at: DateTime<Utc>| at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+
+    if report.events.is_empty() {
+        println!("No current events.");
+    }
+    for (index, current) in report.events.iter().enumerate() {
+        println!(
+            "{} {} ({}), ends {}",
+            if index == 0 { "*" } else { "-" },
+            current.event.summary(),
+            current.event.calendar,
+            local(current.event.end)
+        );
+        println!("    matched: {}", current.resolution.explain());
+        println!(
+            "    status:  {} {}",
+            current.resolution.status.emoji, current.resolution.status.message
+        );
+    }
+
+    match (report.resolved.status(), report.resolved.reason()) {
+        (Some(status), Some(reason)) => println!("Resolved status: {} {} ({reason})", status.emoji, status.message),
+        (Some(status), None) => println!("Resolved status: {} {}", status.emoji, status.message),
+        (None, reason) => println!("Resolved status: {}", reason.unwrap_or_default()),
+    }
+    match report.last_set {
+        Some((last_set, at)) => println!("Last pushed: {} {} at {}", last_set.emoji, last_set.message, local(at)),
+        None => println!("Last pushed: never"),
+    }
+    match report.next_transition {
+        Some(next) => println!("Next transition: {}", local(next)),
+        None => println!("Next transition: none scheduled"),
+    }
+    Ok(())
+}
+
+pub async fn run_set_default(message: Option<String>, emoji: Option<String>) -> Result<(), Error> {
+    update_default_mapping(message, emoji)?;
+    println!("Default status updated");
+    refresh_status_now(&load_config()?).await;
+    Ok(())
+}
+
+pub async fn run_add_mapping(event: String, message: Option<String>, emoji: Option<String>) -> Result<(), Error> {
+    add_mapping(event.clone(), message, emoji)?;
+    println!("Mapping for `{event}` added/updated");
+    refresh_status_now(&load_config()?).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_start_the_bot() {
+        assert!(matches!(parse(&[]), Ok(Command::Bot)));
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_arguments() {
+        assert_eq!(parse(&["serve"]).unwrap_err(), "Unknown command: serve");
+        assert_eq!(parse(&["run", "--user", "1"]).unwrap_err(), "Unexpected argument: --user");
+        assert_eq!(parse(&["status", "--verbose"]).unwrap_err(), "Unexpected argument: --verbose");
+    }
+
+    #[test]
+    fn reports_missing_arguments() {
+        assert_eq!(parse(&["lint"]).unwrap_err(), "Missing <file.ics>");
+        assert_eq!(parse(&["simulate"]).unwrap_err(), "Missing <from>");
+        assert_eq!(parse(&["simulate", "2026-10-20"]).unwrap_err(), "Missing <to>");
+        assert_eq!(parse(&["add-mapping"]).unwrap_err(), "Missing <event>");
+        assert_eq!(parse(&["check", "--user"]).unwrap_err(), "Missing value for --user");
+        assert_eq!(parse(&["set-default"]).unwrap_err(), "Provide at least one of --message or --emoji");
+    }
+
+    #[test]
+    fn lint_takes_every_remaining_argument_as_a_file() {
+        let Ok(Command::Lint { files }) = parse(&["lint", "work.ics", "home.ics", "shared/team.ics"]) else {
+            panic!("expected a lint command");
+        };
+        assert_eq!(files, ["work.ics", "home.ics", "shared/team.ics"]);
+    }
+
+    #[test]
+    fn parses_simulate_times_and_user() {
+        let Ok(Command::Simulate { from, to, user_id }) =
+            parse(&["simulate", "2026-10-20T09:00:00Z", "2026-10-20T17:00:00Z", "--user", "42"])
+        else {
+            panic!("expected a simulate command");
+        };
+        assert_eq!(from, Utc.with_ymd_and_hms(2026, 10, 20, 9, 0, 0).unwrap());
+        assert_eq!(to, Utc.with_ymd_and_hms(2026, 10, 20, 17, 0, 0).unwrap());
+        assert_eq!(user_id, Some(42));
+
+        assert_eq!(
+            parse(&["simulate", "2026-10-20T17:00:00Z", "2026-10-20T09:00:00Z"]).unwrap_err(),
+            "<to> must be after <from>"
+        );
+        assert_eq!(parse(&["simulate", "tomorrow", "2026-10-20"]).unwrap_err(), "Invalid <from>: tomorrow");
+        assert_eq!(parse(&["status", "--user", "me"]).unwrap_err(), "Invalid user id: me");
+    }
+
+    #[test]
+    fn parses_mapping_options() {
+        let Ok(Command::AddMapping { event, message, emoji }) =
+            parse(&["add-mapping", "Standup", "--emoji", "🗣️", "--message", "In standup"])
+        else {
+            panic!("expected an add-mapping command");
+        };
+        assert_eq!(event, "Standup");
+        assert_eq!(message.as_deref(), Some("In standup"));
+        assert_eq!(emoji.as_deref(), Some("🗣️"));
+    }
+}
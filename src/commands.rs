@@ -1,10 +1,22 @@
 use poise::serenity_prelude as serenity;
+use crate::calendar::{
+    CalendarEvent, CalendarInfo, calendar_health, calendar_metadata, calendar_paths, get_current_event, get_events_between,
+    load_calendar_metadata, load_events, read_calendar_info, update_calendar_metadata,
+};
 use crate::config::{
     add_mapping, remove_mapping, list_mappings, update_default_mapping, 
-    load_config
+    load_config, resolve_mapping
 };
-use std::fs;
-use std::path::Path;
+use crate::connection::{refresh_status, wake_status_loop};
+use crate::error::Error as StoreError;
+use crate::history::{CalendarDiff, diff_calendars};
+use crate::lint::{LintReport, lint_calendar};
+use crate::report::status_report;
+use crate::state::{StatusOverride, SyncPause, load_state, update_state};
+use crate::status::Status;
+use crate::store::CalendarStore;
+use crate::util::{format_bytes, parse_datetime, parse_until};
+use chrono::{DateTime, Days, Local, TimeZone, Utc};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -12,6 +24,25 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 // User data, which is stored and accessible in all command invocations
 pub struct Data {}
 
+/// Force a refresh of the status in case a config change affects it
+fn refresh_after_config_change() {
+    if let Ok(config) = load_config() {
+        refresh_status(&config);
+    }
+}
+
+/// Check that the invoking user is the one whose status the bot manages, telling them if not
+///
+/// Returns `false` when the command should stop, after replying.
+async fn ensure_status_owner(ctx: Context<'_>) -> Result<bool, Error> {
+    let owner = load_config().map(|config| config.discord.user_id).ok();
+    if owner == Some(ctx.author().id.get()) {
+        return Ok(true);
+    }
+    ctx.say("❌ Only the account whose status this bot manages can do that.").await?;
+    Ok(false)
+}
+
 /// Add or update an event mapping
 #[poise::command(slash_command)]
 pub async fn add_event_mapping(
@@ -22,6 +53,7 @@ pub async fn add_event_mapping(
 ) -> Result<(), Error> {
     match add_mapping(event.clone(), message.clone(), emoji.clone()) {
         Ok(()) => {
+            refresh_after_config_change();
             let response = format!(
                 "✅ Successfully added/updated mapping for event: `{}`\n{}{}",
                 event,
@@ -45,6 +77,7 @@ pub async fn remove_event_mapping(
 ) -> Result<(), Error> {
     match remove_mapping(&event) {
         Ok(true) => {
+            refresh_after_config_change();
             ctx.say(format!("✅ Successfully removed mapping for event: `{}`", event)).await?;
         }
         Ok(false) => {
@@ -88,7 +121,7 @@ pub async fn list_event_mappings(ctx: Context<'_>) -> Result<(), Error> {
 
             // Discord has a 2000 character limit for messages
             if response.len() > 1900 {
-                response.truncate(1900);
+                response.truncate(response.floor_char_boundary(1900));
                 response.push_str("\n... (truncated)");
             }
             
@@ -101,6 +134,54 @@ pub async fn list_event_mappings(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Preview which mapping an event title would match, without touching your status
+///
+/// With a time instead of a title, previews the event active in your calendars at that time.
+#[poise::command(slash_command)]
+pub async fn test_mapping(
+    ctx: Context<'_>,
+    #[description = "Event title to test"] event: Option<String>,
+    #[description = "Test the event active at this time, e.g. 30m, 17:30 or 2026-10-20T09:30"] time: Option<String>,
+) -> Result<(), Error> {
+    let (subject, matched, status) = match (event, time) {
+        (Some(event), None) => {
+            let resolution = resolve_mapping(&event);
+            (format!("`{}`", event), resolution.explain(), resolution.status)
+        }
+        (None, Some(time)) => {
+            let Some(at) = parse_until(&time, chrono::Utc::now()).or_else(|| parse_datetime(&time)) else {
+                ctx.say("❌ Invalid time. Use a duration like `30m`, a clock time like `17:30`, or a date and time like `2026-10-20T09:30`.").await?;
+                return Ok(());
+            };
+            let subject = format!("<t:{}:f>", at.timestamp());
+            match get_current_event(ctx.author().id.get(), at) {
+                Some(event) => {
+                    let resolution = resolve_mapping(event.summary());
+                    (format!("{} (`{}`)", subject, event.summary()), resolution.explain(), resolution.status)
+                }
+                None => {
+                    let default = load_config().map(|config| config.mappings.default).unwrap_or_default();
+                    (subject, "no event is active then, using the default".to_string(), default)
+                }
+            }
+        }
+        _ => {
+            ctx.say("⚠️ Please provide either an event title or a time to test.").await?;
+            return Ok(());
+        }
+    };
+
+    let response = format!(
+        "🧪 **Mapping Preview for** {}\n\nMatched: {}\nMessage: `{}`\nEmoji: `{}`\n\n*Your status was not changed.*",
+        subject,
+        matched,
+        if status.message.is_empty() { "(none)" } else { &status.message },
+        if status.emoji.is_empty() { "(none)" } else { &status.emoji }
+    );
+    ctx.say(response).await?;
+    Ok(())
+}
+
 /// Update default mapping settings
 #[poise::command(slash_command)]
 pub async fn update_default(
@@ -115,6 +196,7 @@ pub async fn update_default(
 
     match update_default_mapping(message.clone(), emoji.clone()) {
         Ok(()) => {
+            refresh_after_config_change();
             let response = format!(
                 "✅ Successfully updated default settings:\n{}{}",
                 message.as_ref().map(|m| format!("Default message: {}\n", m)).unwrap_or_default(),
@@ -162,8 +244,10 @@ pub async fn upload_calendar(
     ctx: Context<'_>,
     #[description = "Calendar file to upload (.ics format)"] attachment: serenity::Attachment,
     #[description = "Name for the calendar file (without .ics extension)"] name: Option<String>,
+    #[description = "Replace an existing calendar with the same name, keeping the old version"] replace: Option<bool>,
 ) -> Result<(), Error> {
     let user_id = ctx.author().id.get();
+    let replace = replace.unwrap_or(false);
     
     // Validate file extension
     if !attachment.filename.ends_with(".ics") {
@@ -171,37 +255,28 @@ pub async fn upload_calendar(
         return Ok(());
     }
     
-    // Create directory for user if it doesn't exist
-    let user_dir = format!("calendars/{}", user_id);
-    if let Err(e) = fs::create_dir_all(&user_dir) {
-        ctx.say(format!("❌ Failed to create calendar directory: {}", e)).await?;
+    let store = CalendarStore::for_user(user_id);
+    if let Err(e) = store.check_attachment_size(attachment.size.into()) {
+        ctx.say(format!("❌ {}", e)).await?;
         return Ok(());
     }
+
+    // Determine filename, never trusting the attachment's name as a path
+    let Some(filename) = CalendarStore::sanitize_name(name.as_deref().unwrap_or(&attachment.filename)) else {
+        ctx.say("❌ Invalid calendar name. Please use only alphanumeric characters, underscores, or hyphens.").await?;
+        return Ok(());
+    };
     
-    // Determine filename
-    let filename = if let Some(custom_name) = name {
-        // Sanitize the custom name and ensure .ics extension
-        let sanitized = custom_name
-            .chars()
-            .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
-            .collect::<String>();
-        
-        if sanitized.is_empty() {
-            ctx.say("❌ Invalid calendar name. Please use only alphanumeric characters, underscores, or hyphens.").await?;
+    // Check if file already exists
+    let exists = match store.exists(&filename) {
+        Ok(exists) => exists,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
             return Ok(());
         }
-        
-        format!("{}.ics", sanitized)
-    } else {
-        // Use original filename
-        attachment.filename.clone()
     };
-    
-    let file_path = format!("{}/{}", user_dir, filename);
-    
-    // Check if file already exists
-    if Path::new(&file_path).exists() {
-        ctx.say(format!("⚠️ A calendar with the name `{}` already exists. Please choose a different name or remove the existing calendar first.", filename)).await?;
+    if exists && !replace {
+        ctx.say(format!("⚠️ A calendar with the name `{}` already exists. Please choose a different name, or upload again with `replace: True` to swap it out.", filename)).await?;
         return Ok(());
     }
     
@@ -211,20 +286,61 @@ pub async fn upload_calendar(
             // Basic validation - try to parse as ICS
             match std::str::from_utf8(&content) {
                 Ok(content_str) => {
-                    // Basic ICS format validation
-                    if !content_str.contains("BEGIN:VCALENDAR") || !content_str.contains("END:VCALENDAR") {
-                        ctx.say("❌ Invalid calendar file format. Please ensure it's a valid .ics file.").await?;
+                    // Reject files with errors, accept ones that only have warnings
+                    let report = lint_calendar(content_str);
+                    if report.has_errors() {
+                        ctx.say(format!(
+                            "❌ Calendar file has {} error(s), so it wasn't saved:\n{}",
+                            report.errors(),
+                            format_lint_issues(&report)
+                        )).await?;
                         return Ok(());
                     }
                     
+                    if exists {
+                        let previous = store.read_to_string(&filename).unwrap_or_default();
+                        let diff = diff_calendars(&previous, content_str);
+                        match store.replace(&filename, &content) {
+                            Ok(_) => {
+                                refresh_after_config_change();
+                                let mut response = format!(
+                                    "✅ Replaced calendar `{}`. The previous version was kept and can be restored.\n\n{}",
+                                    filename,
+                                    format_calendar_diff(&diff)
+                                );
+                                if report.warnings() > 0 {
+                                    response.push_str(&format!(
+                                        "\n\n⚠️ {} warning(s):\n{}",
+                                        report.warnings(),
+                                        format_lint_issues(&report)
+                                    ));
+                                }
+                                ctx.say(response).await?;
+                            }
+                            Err(e) => {
+                                ctx.say(format!("❌ Failed to replace calendar file, the old one is still in place: {}", e)).await?;
+                            }
+                        }
+                        return Ok(());
+                    }
+
                     // Save the file
-                    match fs::write(&file_path, content) {
-                        Ok(()) => {
-                            ctx.say(format!(
+                    match store.create(&filename, &content) {
+                        Ok(file_path) => {
+                            refresh_after_config_change();
+                            let mut response = format!(
                                 "✅ Successfully uploaded calendar `{}` to your calendar directory!\n📁 File saved as: `{}`",
                                 filename,
-                                file_path
-                            )).await?;
+                                file_path.display()
+                            );
+                            if report.warnings() > 0 {
+                                response.push_str(&format!(
+                                    "\n\n⚠️ {} warning(s):\n{}",
+                                    report.warnings(),
+                                    format_lint_issues(&report)
+                                ));
+                            }
+                            ctx.say(response).await?;
                         }
                         Err(e) => {
                             ctx.say(format!("❌ Failed to save calendar file: {}", e)).await?;
@@ -244,52 +360,355 @@ pub async fn upload_calendar(
     Ok(())
 }
 
+/// Issues shown in a single Discord message before the rest are summarised
+const MAX_LISTED_ISSUES: usize = 15;
+
+fn format_lint_issues(report: &LintReport) -> String {
+    let mut issues = report
+        .issues
+        .iter()
+        .take(MAX_LISTED_ISSUES)
+        .map(|issue| format!("• {}", issue))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if report.issues.len() > MAX_LISTED_ISSUES {
+        issues.push_str(&format!("\n... and {} more", report.issues.len() - MAX_LISTED_ISSUES));
+    }
+    issues
+}
+
+/// Check a calendar file for problems without uploading it
+#[poise::command(slash_command)]
+pub async fn check_calendar(
+    ctx: Context<'_>,
+    #[description = "Calendar file to check (.ics format)"] attachment: Option<serenity::Attachment>,
+    #[description = "Name of an uploaded calendar to check (default: all of them)"] filename: Option<String>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+
+    let files = if let Some(attachment) = attachment {
+        if let Err(e) = CalendarStore::for_user(user_id).check_attachment_size(attachment.size.into()) {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+        let content = match attachment.download().await {
+            Ok(content) => content,
+            Err(e) => {
+                ctx.say(format!("❌ Failed to download attachment: {}", e)).await?;
+                return Ok(());
+            }
+        };
+        vec![(attachment.filename.clone(), String::from_utf8_lossy(&content).into_owned())]
+    } else {
+        // Only ever read files that are actually in the user's directory
+        let store = CalendarStore::for_user(user_id);
+        match &filename {
+            Some(filename) => match store.read_to_string(filename) {
+                Ok(contents) => vec![(filename.clone(), contents)],
+                Err(StoreError::CalendarNotFound { .. }) => Vec::new(),
+                Err(e) => {
+                    ctx.say(format!("❌ {}", e)).await?;
+                    return Ok(());
+                }
+            },
+            None => store
+                .list()
+                .iter()
+                .filter_map(|path| {
+                    let name = path.file_name()?.to_str()?.to_string();
+                    let contents = store.read_to_string(&name).ok()?;
+                    Some((name, contents))
+                })
+                .collect(),
+        }
+    };
+
+    if files.is_empty() {
+        ctx.say(match filename {
+            Some(filename) => format!("⚠️ Calendar file `{}` not found.", filename),
+            None => "📅 No calendar files found. Attach one or use `/upload_calendar` first!".to_string(),
+        }).await?;
+        return Ok(());
+    }
+
+    let mut response = String::from("🩺 **Calendar Check:**\n\n");
+    for (name, contents) in files {
+        let report = lint_calendar(&contents);
+        let verdict = if report.has_errors() {
+            "❌"
+        } else if report.warnings() > 0 {
+            "⚠️"
+        } else {
+            "✅"
+        };
+        response.push_str(&format!(
+            "{} `{}`: {} events, {} errors, {} warnings\n",
+            verdict,
+            name,
+            report.events,
+            report.errors(),
+            report.warnings()
+        ));
+        if !report.issues.is_empty() {
+            response.push_str(&format_lint_issues(&report));
+            response.push_str("\n\n");
+        }
+    }
+
+    // Discord has a 2000 character limit for messages
+    if response.len() > 1900 {
+        response.truncate(response.floor_char_boundary(1900));
+        response.push_str("\n... (truncated)");
+    }
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Summarise which events a replacement added, removed or changed
+fn format_calendar_diff(diff: &CalendarDiff) -> String {
+    if diff.is_empty() {
+        return "No events changed.".to_string();
+    }
+
+    let mut text = format!(
+        "**Changes:** {} added, {} removed, {} changed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    for (symbol, events) in [("+", &diff.added), ("-", &diff.removed), ("~", &diff.changed)] {
+        for event in events.iter().take(5) {
+            text.push_str(&format!("\n`{}` {}", symbol, event));
+        }
+        if events.len() > 5 {
+            text.push_str(&format!("\n`{}` ... and {} more", symbol, events.len() - 5));
+        }
+    }
+    text
+}
+
 /// List uploaded calendars
 #[poise::command(slash_command)]
 pub async fn list_calendars(ctx: Context<'_>) -> Result<(), Error> {
     let user_id = ctx.author().id.get();
-    let user_dir = format!("calendars/{}", user_id);
+    let store = CalendarStore::for_user(user_id);
+    let calendar_files = store.list();
+
+    if calendar_files.is_empty() {
+        ctx.say("📅 No calendar files found. Use `/upload_calendar` to add one!").await?;
+        return Ok(());
+    }
     
-    match fs::read_dir(&user_dir) {
-        Ok(entries) => {
-            let calendar_files: Vec<_> = entries
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("ics"))
-                .collect();
-            
-            if calendar_files.is_empty() {
-                ctx.say("📅 No calendar files found. Use `/upload_calendar` to add one!").await?;
-                return Ok(());
+    // Re-read the calendars so the health shown below is current
+    load_events(user_id, chrono::Utc::now());
+
+    let calendar_metadata = load_calendar_metadata(user_id);
+    let mut response = String::from("📅 **Your Uploaded Calendars:**\n\n");
+    
+    for path in calendar_files {
+        let filename = path.file_name().unwrap_or_default();
+        let filename_str = filename.to_string_lossy();
+        let settings = calendar_metadata.get(filename_str.as_ref()).cloned().unwrap_or_default();
+
+        let mut line = format!("• `{}`", filename_str);
+        if let Some(display_name) = &settings.display_name {
+            line.push_str(&format!(" \"{}\"", display_name));
+        }
+        // Get file size
+        if let Ok(metadata) = path.metadata() {
+            line.push_str(&format!(" ({} KB)", metadata.len() / 1024));
+        }
+        if !settings.enabled {
+            line.push_str(" — disabled");
+        }
+        response.push_str(&line);
+        response.push('\n');
+
+        if !settings.enabled {
+            continue;
+        }
+
+        match calendar_health(&path) {
+            Some(health) if health.is_healthy() => {
+                response.push_str(&format!("  ✅ {} events, read <t:{}:R>\n", health.events, health.checked_at.timestamp()));
             }
-            
-            let mut response = String::from("📅 **Your Uploaded Calendars:**\n\n");
-            
-            for entry in calendar_files {
-                let filename = entry.file_name();
-                let filename_str = filename.to_string_lossy();
-                
-                // Get file size
-                if let Ok(metadata) = entry.metadata() {
-                    let size_kb = metadata.len() / 1024;
-                    response.push_str(&format!("• `{}` ({} KB)\n", filename_str, size_kb));
+            Some(health) => {
+                if let Some(error) = &health.error {
+                    response.push_str(&format!("  ❌ {}\n", error));
                 } else {
-                    response.push_str(&format!("• `{}`\n", filename_str));
+                    response.push_str(&format!("  ⚠️ {} events, {} skipped:\n", health.events, health.skipped.len()));
+                }
+                for skipped in health.skipped.iter().take(3) {
+                    response.push_str(&format!("    - {}\n", skipped));
                 }
             }
-            
-            // Discord has a 2000 character limit for messages
-            if response.len() > 1900 {
-                response.truncate(1900);
-                response.push_str("\n... (truncated)");
-            }
-            
-            ctx.say(response).await?;
-        }
-        Err(_) => {
-            ctx.say("📅 No calendar directory found. Use `/upload_calendar` to add your first calendar!").await?;
+            None => response.push_str("  Not read yet\n"),
         }
     }
+
+    let (usage, limits) = (store.usage(), store.limits());
+    response.push_str(&format!(
+        "\n💾 Using {} of {}, {} of {} calendars",
+        format_bytes(usage.bytes),
+        format_bytes(limits.max_bytes_per_user),
+        usage.calendars,
+        limits.max_calendars_per_user
+    ));
+    
+    // Discord has a 2000 character limit for messages
+    if response.len() > 1900 {
+        response.truncate(response.floor_char_boundary(1900));
+        response.push_str("\n... (truncated)");
+    }
     
+    ctx.say(response).await?;
+
+    Ok(())
+}
+
+/// Render one calendar's statistics for `/calendar_info`
+fn format_calendar_info(info: &CalendarInfo) -> String {
+    let timestamp = |time: Option<DateTime<Utc>>| match time {
+        Some(time) => format!("<t:{}:f>", time.timestamp()),
+        None => "never".to_string(),
+    };
+
+    let mut text = format!("📅 **{}**", info.metadata.display_name.as_deref().unwrap_or(&info.file));
+    if info.metadata.display_name.is_some() {
+        text.push_str(&format!(" (`{}`)", info.file));
+    }
+    text.push('\n');
+    if let Some(name) = &info.name {
+        text.push_str(&format!("Calendar name: {}\n", name));
+    }
+    text.push_str(&format!("Enabled: {}\n", if info.metadata.enabled { "yes" } else { "no" }));
+    text.push_str(&format!("Priority: {}\n", info.metadata.priority));
+    if let Some(label) = &info.metadata.label {
+        text.push_str(&format!("Label: {}\n", label));
+    }
+    text.push_str(&format!("Source: {}\n", info.metadata.source));
+    text.push_str(&format!("Events: {} ({} recurring)\n", info.events, info.recurring));
+    if info.zoned > 0 {
+        text.push_str(&format!("⚠️ {} events use a time zone, which is read as UTC\n", info.zoned));
+    }
+    if let Some((first, last)) = info.range {
+        text.push_str(&format!("Covers: <t:{}:d> to <t:{}:d>\n", first.timestamp(), last.timestamp()));
+    }
+    text.push_str(&format!("Updated: {}\n", timestamp(info.modified)));
+    text.push_str(&format!("Last read: {}\n", timestamp(info.last_read)));
+
+    if let Some(error) = &info.error {
+        text.push_str(&format!("❌ {}\n", error));
+    }
+    if !info.skipped.is_empty() {
+        text.push_str(&format!("⚠️ {} events skipped:\n", info.skipped.len()));
+        for skipped in info.skipped.iter().take(3) {
+            text.push_str(&format!("- {}\n", skipped));
+        }
+    }
+    text
+}
+
+/// Show statistics about your calendars
+#[poise::command(slash_command)]
+pub async fn calendar_info(
+    ctx: Context<'_>,
+    #[description = "Only show this calendar file (with .ics extension)"] filename: Option<String>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    let mut paths = calendar_paths(user_id);
+    paths.sort();
+    if let Some(filename) = &filename {
+        paths.retain(|path| path.file_name().is_some_and(|name| name == filename.as_str()));
+        if paths.is_empty() {
+            ctx.say(format!("⚠️ Calendar file `{}` not found.", filename)).await?;
+            return Ok(());
+        }
+    }
+
+    if paths.is_empty() {
+        ctx.say("📅 No calendar files found. Use `/upload_calendar` to add one!").await?;
+        return Ok(());
+    }
+
+    let metadata = load_calendar_metadata(user_id);
+    let mut response = String::new();
+    for path in &paths {
+        let filename = path.file_name().unwrap_or_default().to_string_lossy();
+        let settings = metadata.get(filename.as_ref()).cloned().unwrap_or_default();
+        response.push_str(&format_calendar_info(&read_calendar_info(path, settings)));
+        response.push('\n');
+    }
+
+    // Discord has a 2000 character limit for messages
+    if response.len() > 1900 {
+        response.truncate(response.floor_char_boundary(1900));
+        response.push_str("\n... (truncated)");
+    }
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// List the kept previous versions of a calendar
+#[poise::command(slash_command)]
+pub async fn calendar_history(
+    ctx: Context<'_>,
+    #[description = "Name of the calendar file (with .ics extension)"] filename: String,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    let versions = match CalendarStore::for_user(user_id).versions(&filename) {
+        Ok(versions) => versions,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    if versions.is_empty() {
+        ctx.say(format!("📜 No previous versions of `{}` are kept. Versions are kept when a calendar is replaced.", filename)).await?;
+        return Ok(());
+    }
+
+    let mut response = format!("📜 **Previous versions of `{}`** (newest first):\n\n", filename);
+    for (number, version) in versions.iter().enumerate() {
+        response.push_str(&format!(
+            "`{}` <t:{}:f> — {} events\n",
+            number + 1,
+            version.saved_at.timestamp(),
+            version.events
+        ));
+    }
+    response.push_str("\nUse `/rollback_calendar` with a version number to restore one.");
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Restore a previous version of a calendar
+#[poise::command(slash_command)]
+pub async fn rollback_calendar(
+    ctx: Context<'_>,
+    #[description = "Name of the calendar file (with .ics extension)"] filename: String,
+    #[description = "Version number from /calendar_history (1 is the newest)"]
+    #[min = 1]
+    version: Option<usize>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    match CalendarStore::for_user(user_id).rollback(&filename, version.unwrap_or(1)) {
+        Ok(restored) => {
+            refresh_after_config_change();
+            ctx.say(format!(
+                "⏪ Restored `{}` to the version from <t:{}:f> ({} events). The version it replaced was kept.",
+                filename,
+                restored.saved_at.timestamp(),
+                restored.events
+            )).await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to roll back calendar: {}", e)).await?;
+        }
+    }
     Ok(())
 }
 
@@ -301,19 +720,12 @@ pub async fn remove_calendar(
 ) -> Result<(), Error> {
     let user_id = ctx.author().id.get();
     
-    // Validate filename has .ics extension
-    if !filename.ends_with(".ics") {
-        ctx.say("❌ Please specify a calendar filename with the .ics extension.").await?;
-        return Ok(());
-    }
-    
-    let file_path = format!("calendars/{}/{}", user_id, filename);
-    
-    match fs::remove_file(&file_path) {
+    match CalendarStore::for_user(user_id).remove(&filename) {
         Ok(()) => {
-            ctx.say(format!("✅ Successfully removed calendar `{}`", filename)).await?;
+            refresh_after_config_change();
+            ctx.say(format!("✅ Successfully removed calendar `{}`. It can still be restored with `/rollback_calendar`.", filename)).await?;
         }
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        Err(StoreError::CalendarNotFound { .. }) => {
             ctx.say(format!("⚠️ Calendar file `{}` not found.", filename)).await?;
         }
         Err(e) => {
@@ -324,6 +736,508 @@ pub async fn remove_calendar(
     Ok(())
 }
 
+/// Whether the user has a calendar file with this name
+fn has_calendar(user_id: u64, filename: &str) -> bool {
+    CalendarStore::for_user(user_id).exists(filename).unwrap_or(false)
+}
+
+/// Enable or disable a calendar without deleting it
+#[poise::command(slash_command)]
+pub async fn toggle_calendar(
+    ctx: Context<'_>,
+    #[description = "Name of the calendar file (with .ics extension)"] filename: String,
+    #[description = "Whether the calendar should affect your status; flips it if left out"] enabled: Option<bool>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    if !has_calendar(user_id, &filename) {
+        ctx.say(format!("⚠️ Calendar file `{}` not found.", filename)).await?;
+        return Ok(());
+    }
+
+    let result = update_calendar_metadata(user_id, &filename, |metadata| {
+        metadata.enabled = enabled.unwrap_or(!metadata.enabled);
+        metadata.enabled
+    });
+    match result {
+        Ok(true) => {
+            refresh_after_config_change();
+            ctx.say(format!("✅ Enabled calendar `{}`", filename)).await?;
+        }
+        Ok(false) => {
+            refresh_after_config_change();
+            ctx.say(format!("⏸️ Disabled calendar `{}`. Its events no longer affect your status.", filename)).await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to update calendar: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Give a calendar a display name
+#[poise::command(slash_command)]
+pub async fn rename_calendar(
+    ctx: Context<'_>,
+    #[description = "Name of the calendar file (with .ics extension)"] filename: String,
+    #[description = "Name to show instead of the filename; leave out to clear it"] name: Option<String>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    if !has_calendar(user_id, &filename) {
+        ctx.say(format!("⚠️ Calendar file `{}` not found.", filename)).await?;
+        return Ok(());
+    }
+
+    let name = name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+    match update_calendar_metadata(user_id, &filename, |metadata| metadata.display_name = name.clone()) {
+        Ok(()) => match name {
+            Some(name) => ctx.say(format!("✅ Calendar `{}` is now shown as **{}**", filename, name)).await?,
+            None => ctx.say(format!("✅ Cleared the display name of `{}`", filename)).await?,
+        },
+        Err(e) => ctx.say(format!("❌ Failed to update calendar: {}", e)).await?,
+    };
+    Ok(())
+}
+
+/// Set a calendar's priority and label
+#[poise::command(slash_command)]
+pub async fn configure_calendar(
+    ctx: Context<'_>,
+    #[description = "Name of the calendar file (with .ics extension)"] filename: String,
+    #[description = "Higher priority calendars win when events overlap (default 0)"] priority: Option<i32>,
+    #[description = "Color or label, e.g. work or #ff8800"] label: Option<String>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    if !has_calendar(user_id, &filename) {
+        ctx.say(format!("⚠️ Calendar file `{}` not found.", filename)).await?;
+        return Ok(());
+    }
+
+    if priority.is_none() && label.is_none() {
+        let metadata = calendar_metadata(user_id, &filename);
+        ctx.say(format!(
+            "📅 `{}`: priority {}, label {}",
+            filename,
+            metadata.priority,
+            metadata.label.as_deref().unwrap_or("none")
+        )).await?;
+        return Ok(());
+    }
+
+    let result = update_calendar_metadata(user_id, &filename, |metadata| {
+        if let Some(priority) = priority {
+            metadata.priority = priority;
+        }
+        if let Some(label) = label {
+            metadata.label = Some(label).filter(|label| !label.trim().is_empty());
+        }
+        metadata.clone()
+    });
+    match result {
+        Ok(metadata) => {
+            refresh_after_config_change();
+            ctx.say(format!(
+                "✅ Updated `{}`: priority {}, label {}",
+                filename,
+                metadata.priority,
+                metadata.label.as_deref().unwrap_or("none")
+            )).await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to update calendar: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Resume automatic status updates after a manual status change
+#[poise::command(slash_command)]
+pub async fn resume(ctx: Context<'_>) -> Result<(), Error> {
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    // Adopt the held status as ours, or the next check would see it still showing and hold it again
+    let resumed = update_state(|state| {
+        let hold = state.manual_hold.take()?;
+        state.last_set = Some(hold.status.clone());
+        state.last_set_from_event = false;
+        Some(hold)
+    });
+    match resumed {
+        Ok(Some(hold)) => {
+            refresh_after_config_change();
+            ctx.say(format!(
+                "▶️ Resumed automatic updates. Your manual status `{} {}` will be replaced by your calendar status.",
+                hold.status.emoji, hold.status.message
+            )).await?;
+        }
+        Ok(None) => {
+            ctx.say("⚠️ Automatic updates aren't paused for a manual status.").await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to resume automatic updates: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Pin a status for a while, taking precedence over calendar events
+#[poise::command(slash_command)]
+pub async fn override_status(
+    ctx: Context<'_>,
+    #[description = "Discord status message"] message: String,
+    #[description = "Discord status emoji"] emoji: String,
+    #[description = "How long, e.g. 30m, 2h or 1h30m, or a clock time like 17:30"] duration: String,
+) -> Result<(), Error> {
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    let Some(until) = parse_until(&duration, chrono::Utc::now()) else {
+        ctx.say("❌ Invalid duration. Use something like `30m`, `2h`, `1h30m` or a clock time like `17:30`.").await?;
+        return Ok(());
+    };
+
+    let status_override = StatusOverride {
+        status: Status { message, emoji },
+        until,
+    };
+    let response = format!(
+        "📌 Status pinned to `{} {}` until <t:{}:t> (<t:{}:R>).",
+        status_override.status.emoji,
+        status_override.status.message,
+        until.timestamp(),
+        until.timestamp()
+    );
+
+    match update_state(|state| state.status_override = Some(status_override)) {
+        Ok(()) => {
+            wake_status_loop();
+            ctx.say(response).await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to save status override: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove a pinned status and go back to calendar-driven updates
+#[poise::command(slash_command)]
+pub async fn clear_override(ctx: Context<'_>) -> Result<(), Error> {
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    match update_state(|state| state.status_override.take()) {
+        Ok(Some(_)) => {
+            wake_status_loop();
+            ctx.say("✅ Status override cleared, calendar updates resumed.").await?;
+        }
+        Ok(None) => {
+            ctx.say("⚠️ No status override is active.").await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to clear status override: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stop the bot from touching your status, optionally for a limited time
+#[poise::command(slash_command)]
+pub async fn pause_sync(
+    ctx: Context<'_>,
+    #[description = "How long, e.g. 30m, 2h or 1h30m, or a clock time like 17:30 (default: until resumed)"]
+    duration: Option<String>,
+) -> Result<(), Error> {
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now();
+    let until = match duration {
+        Some(duration) => match parse_until(&duration, now) {
+            Some(until) => Some(until),
+            None => {
+                ctx.say("❌ Invalid duration. Use something like `30m`, `2h`, `1h30m` or a clock time like `17:30`.").await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    match update_state(|state| state.sync_pause = Some(SyncPause { since: now, until })) {
+        Ok(()) => {
+            wake_status_loop();
+            let response = match until {
+                Some(until) => format!("⏸️ Calendar sync paused until <t:{}:t> (<t:{}:R>).", until.timestamp(), until.timestamp()),
+                None => "⏸️ Calendar sync paused. Use `/resume_sync` to start it again.".to_string(),
+            };
+            ctx.say(response).await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to pause sync: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Start calendar sync again after `/pause_sync`
+#[poise::command(slash_command)]
+pub async fn resume_sync(ctx: Context<'_>) -> Result<(), Error> {
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    match update_state(|state| state.sync_pause.take()) {
+        Ok(Some(_)) => {
+            wake_status_loop();
+            ctx.say("▶️ Calendar sync resumed.").await?;
+        }
+        Ok(None) => {
+            ctx.say("⚠️ Calendar sync isn't paused.").await?;
+        }
+        Err(e) => {
+            ctx.say(format!("❌ Failed to resume sync: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Show whether sync is paused, overridden or holding a manual status
+#[poise::command(slash_command)]
+pub async fn sync_status(ctx: Context<'_>) -> Result<(), Error> {
+    let state = load_state();
+    let now = chrono::Utc::now();
+    let mut response = String::from("🔄 **Sync Status:**\n\n");
+
+    match state.sync_pause.filter(|pause| pause.until.is_none_or(|until| until > now)) {
+        Some(SyncPause { until: Some(until), .. }) => {
+            response.push_str(&format!("Paused until <t:{}:t> (<t:{}:R>)\n", until.timestamp(), until.timestamp()));
+        }
+        Some(SyncPause { since, until: None }) => {
+            response.push_str(&format!("Paused since <t:{}:f>, until `/resume_sync`\n", since.timestamp()));
+        }
+        None => response.push_str("Sync is running\n"),
+    }
+
+    if let Some(status_override) = state.status_override.filter(|status_override| status_override.until > now) {
+        response.push_str(&format!(
+            "Override: `{} {}` until <t:{}:t>\n",
+            status_override.status.emoji,
+            status_override.status.message,
+            status_override.until.timestamp()
+        ));
+    }
+
+    if let Some(hold) = state.manual_hold {
+        response.push_str(&format!(
+            "Respecting manual status: `{} {}` (use `/resume` to override)\n",
+            hold.status.emoji, hold.status.message
+        ));
+    }
+
+    if let Some(last_set) = state.last_set {
+        response.push_str(&format!("Last set: `{} {}`\n", last_set.emoji, last_set.message));
+    }
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Show what the bot thinks is happening right now and why
+#[poise::command(slash_command)]
+pub async fn status_now(ctx: Context<'_>) -> Result<(), Error> {
+    // The report mixes the user's calendars with the loop's state, which belongs to the status owner
+    if !ensure_status_owner(ctx).await? {
+        return Ok(());
+    }
+
+    let report = status_report(ctx.author().id.get(), chrono::Utc::now());
+    let mut response = String::from("🔎 **Current Status Resolution:**\n\n");
+
+    if report.events.is_empty() {
+        response.push_str("No current events.\n");
+    }
+    for (index, current) in report.events.iter().enumerate() {
+        response.push_str(&format!(
+            "{}**Event:** `{}` from `{}`, ends <t:{}:t>\n  Matched: {}\n  Status: `{} {}`\n",
+            if index == 0 { "▶️ " } else { "• " },
+            current.event.summary(),
+            current.event.calendar,
+            current.event.end.timestamp(),
+            current.resolution.explain(),
+            current.resolution.status.emoji,
+            current.resolution.status.message
+        ));
+    }
+    if report.events.len() > 1 {
+        response.push_str("Only the first event (▶️) sets your status.\n");
+    }
+
+    let resolved = match (report.resolved.status(), report.resolved.reason()) {
+        (Some(status), Some(reason)) => format!("`{} {}` ({})", status.emoji, status.message, reason),
+        (Some(status), None) => format!("`{} {}`", status.emoji, status.message),
+        (None, reason) => reason.unwrap_or_default().to_string(),
+    };
+    response.push_str(&format!("\n**Resolved status:** {}\n", resolved));
+
+    match report.last_set {
+        Some((last_set, at)) => response.push_str(&format!(
+            "**Last pushed:** `{} {}` <t:{}:R>\n",
+            last_set.emoji,
+            last_set.message,
+            at.timestamp()
+        )),
+        None => response.push_str("**Last pushed:** never\n"),
+    }
+
+    match report.next_transition {
+        Some(next) => response.push_str(&format!("**Next transition:** <t:{}:f> (<t:{}:R>)\n", next.timestamp(), next.timestamp())),
+        None => response.push_str("**Next transition:** none scheduled\n"),
+    }
+
+    // Discord has a 2000 character limit for messages
+    if response.len() > 1900 {
+        response.truncate(response.floor_char_boundary(1900));
+        response.push_str("\n... (truncated)");
+    }
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Events shown on each page of `/agenda` and `/upcoming`
+const AGENDA_PAGE_SIZE: usize = 10;
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum AgendaDay {
+    Today,
+    Tomorrow,
+}
+
+/// Midnight in the bot's local time zone, `days` days from today
+fn local_midnight(days: u64) -> Option<DateTime<Utc>> {
+    let date = Local::now().date_naive().checked_add_days(Days::new(days))?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|midnight| midnight.with_timezone(&Utc))
+}
+
+/// Render events as paginated embeds, one line per event with the status it would produce
+async fn send_agenda(ctx: Context<'_>, title: &str, events: &[CalendarEvent]) -> Result<(), Error> {
+    if events.is_empty() {
+        ctx.say(format!("📅 {}: nothing scheduled.", title)).await?;
+        return Ok(());
+    }
+
+    let page_count = events.len().div_ceil(AGENDA_PAGE_SIZE);
+    let pages = events
+        .chunks(AGENDA_PAGE_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut page = format!("📅 **{}** ({}/{})\n\n", title, index + 1, page_count);
+            for event in chunk {
+                let status = resolve_mapping(event.summary()).status;
+                page.push_str(&format!(
+                    "<t:{}:f> – <t:{}:t>\n**{}** → `{} {}`\n*{}*\n\n",
+                    event.start.timestamp(),
+                    event.end.timestamp(),
+                    event.summary(),
+                    status.emoji,
+                    status.message,
+                    event.calendar
+                ));
+            }
+            page
+        })
+        .collect::<Vec<_>>();
+
+    let pages = pages.iter().map(String::as_str).collect::<Vec<_>>();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// List today's or tomorrow's events
+#[poise::command(slash_command)]
+pub async fn agenda(
+    ctx: Context<'_>,
+    #[description = "Which day to show (default: today)"] day: Option<AgendaDay>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    let (offset, title) = match day.unwrap_or(AgendaDay::Today) {
+        AgendaDay::Today => (0, "Today"),
+        AgendaDay::Tomorrow => (1, "Tomorrow"),
+    };
+
+    let (Some(from), Some(to)) = (local_midnight(offset), local_midnight(offset + 1)) else {
+        ctx.say("❌ Couldn't work out the day boundaries.").await?;
+        return Ok(());
+    };
+
+    send_agenda(ctx, title, &get_events_between(user_id, from, to, chrono::Utc::now())).await
+}
+
+/// List events over the next few days
+#[poise::command(slash_command)]
+pub async fn upcoming(
+    ctx: Context<'_>,
+    #[description = "Number of days to look ahead (default: 7)"]
+    #[min = 1]
+    #[max = 31]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+    let days = days.unwrap_or(7).clamp(1, 31);
+    let from = chrono::Utc::now();
+    let to = from + chrono::TimeDelta::days(days.into());
+
+    let title = format!("Next {} day{}", days, if days == 1 { "" } else { "s" });
+    send_agenda(ctx, &title, &get_events_between(user_id, from, to, from)).await
+}
+
+/// Show how much calendar storage each user is using
+#[poise::command(slash_command, owners_only)]
+pub async fn storage_usage(ctx: Context<'_>) -> Result<(), Error> {
+    let limits = load_config().map(|config| config.storage).unwrap_or_default();
+    let usage = CalendarStore::all_usage();
+    if usage.is_empty() {
+        ctx.say("💾 Nobody has uploaded a calendar yet.").await?;
+        return Ok(());
+    }
+
+    let mut response = format!(
+        "💾 **Storage Usage** (limits: {} per file, {} calendars and {} per user)\n\n",
+        format_bytes(limits.max_attachment_bytes),
+        limits.max_calendars_per_user,
+        format_bytes(limits.max_bytes_per_user)
+    );
+    for (user_id, usage) in &usage {
+        let over = usage.calendars > limits.max_calendars_per_user || usage.bytes > limits.max_bytes_per_user;
+        response.push_str(&format!(
+            "{}<@{}>: {} calendars, {} used, {} in previous versions\n",
+            if over { "⚠️ " } else { "" },
+            user_id,
+            usage.calendars,
+            format_bytes(usage.bytes),
+            format_bytes(usage.history_bytes)
+        ));
+    }
+    let total: u64 = usage.iter().map(|(_, usage)| usage.bytes + usage.history_bytes).sum();
+    response.push_str(&format!("\nTotal: {} across {} users", format_bytes(total), usage.len()));
+
+    // Discord has a 2000 character limit for messages
+    if response.len() > 1900 {
+        response.truncate(response.floor_char_boundary(1900));
+        response.push_str("\n... (truncated)");
+    }
+
+    ctx.say(response).await?;
+    Ok(())
+}
+
 /// Show help information about available commands
 #[poise::command(slash_command)]
 pub async fn help_command(ctx: Context<'_>) -> Result<(), Error> {
@@ -331,21 +1245,43 @@ pub async fn help_command(ctx: Context<'_>) -> Result<(), Error> {
 **Calendar2Discord Bot Commands**
 
 **Calendar Management Commands:**
-• `/upload_calendar` - Upload a new .ics calendar file
+• `/upload_calendar` - Upload a new .ics calendar file, or replace one
 • `/list_calendars` - List all your uploaded calendars
+• `/calendar_info` - Show event counts, date ranges and problems per calendar
+• `/toggle_calendar` - Enable or disable a calendar without deleting it
+• `/rename_calendar` - Give a calendar a display name
+• `/configure_calendar` - Set a calendar's priority and label
+• `/calendar_history` - List the kept previous versions of a calendar
+• `/rollback_calendar` - Restore a previous version of a calendar
 • `/remove_calendar` - Remove a calendar file
+• `/check_calendar` - Check a calendar file for problems
+
+**Agenda Commands:**
+• `/agenda` - List today's or tomorrow's events
+• `/upcoming` - List events over the next few days
 
 **Event Mapping Commands:**
 • `/add_event_mapping` - Add or update an event mapping
 • `/remove_event_mapping` - Remove an event mapping
 • `/list_event_mappings` - List all current mappings
+• `/test_mapping` - Preview the status an event title, or the event at a given time, would produce
 
 **Default Settings Commands:**
 • `/update_default` - Update default message/emoji
 • `/show_default` - Show current default settings
 
+**Status Commands:**
+• `/override_status` - Pin a status for a while, ahead of calendar events
+• `/clear_override` - Remove a pinned status
+• `/resume` - Resume automatic updates after you changed your status by hand
+• `/pause_sync` - Stop updating your status, optionally for a while
+• `/resume_sync` - Start updating your status again
+• `/sync_status` - Show whether sync is paused or overridden
+• `/status_now` - Explain the current events and resolved status
+
 **General:**
 • `/help_command` - Show this help message
+• `/storage_usage` - Show calendar storage per user (bot owners only)
 
 **How it works:**
 The bot automatically checks your calendar and sets your Discord status based on current events. You can configure mappings for specific event names to customize your status message and emoji.
@@ -361,11 +1297,29 @@ pub async fn start_discord_bot(token: String) -> Result<(), Error> {
                 add_event_mapping(),
                 remove_event_mapping(),
                 list_event_mappings(),
+                test_mapping(),
                 update_default(),
                 show_default(),
                 upload_calendar(),
                 list_calendars(),
+                calendar_info(),
+                toggle_calendar(),
+                rename_calendar(),
+                configure_calendar(),
+                calendar_history(),
+                rollback_calendar(),
                 remove_calendar(),
+                check_calendar(),
+                override_status(),
+                clear_override(),
+                resume(),
+                pause_sync(),
+                resume_sync(),
+                sync_status(),
+                status_now(),
+                agenda(),
+                upcoming(),
+                storage_usage(),
                 help_command(),
             ],
             ..Default::default()
@@ -1,11 +1,54 @@
 use serde::{Deserialize, Serialize};
-use std::{fs, sync::{atomic::AtomicBool, Arc}};
-use crate::{connection, status::Status};
+use std::fs;
+use crate::{error, sink::{discord, mqtt, slack}, status::Status};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub discord: DiscordConfig,
     pub mappings: Mappings,
+    #[serde(default = "default_sinks")]
+    pub sinks: Vec<SinkConfig>,
+    #[serde(default)]
+    pub manual_status_policy: ManualStatusPolicy,
+    #[serde(default)]
+    pub storage: StorageLimits,
+}
+
+/// Limits on what each user can keep in their calendar directory
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StorageLimits {
+    /// Largest attachment `/upload_calendar` will download
+    #[serde(default = "default_max_attachment_bytes")]
+    pub max_attachment_bytes: u64,
+    #[serde(default = "default_max_calendars_per_user")]
+    pub max_calendars_per_user: usize,
+    /// Combined size of a user's current calendars; their kept previous versions are capped at the
+    /// same size separately, dropping the oldest first
+    #[serde(default = "default_max_bytes_per_user")]
+    pub max_bytes_per_user: u64,
+}
+
+impl Default for StorageLimits {
+    fn default() -> Self {
+        Self {
+            max_attachment_bytes: default_max_attachment_bytes(),
+            max_calendars_per_user: default_max_calendars_per_user(),
+            max_bytes_per_user: default_max_bytes_per_user(),
+        }
+    }
+}
+
+/// What to do when the user changes their status by hand
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManualStatusPolicy {
+    /// Keep applying calendar statuses regardless
+    #[default]
+    AlwaysOverride,
+    /// Leave the manual status alone until it expires or the user runs `/resume`
+    RespectManual,
+    /// Like `RespectManual`, but also resume once a different event starts
+    RespectUntilNextEvent,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,13 +64,91 @@ pub struct Mappings {
     pub mapping: Vec<EventMapping>,
 }
 
-impl Default for Status {
-    fn default() -> Self {
-        Self {
-            message: String::new(),
-            emoji: String::new(),
-        }
-    }
+/// Backends the resolved status is pushed to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Discord {
+        /// User token, falling back to the `DISCORD_USER_TOKEN` environment variable
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+        #[serde(default = "default_discord_api_base")]
+        api_base: String,
+    },
+    Slack {
+        /// User OAuth token with the `users.profile:write` scope
+        token: String,
+        #[serde(default = "default_slack_api_base")]
+        api_base: String,
+    },
+    Matrix {
+        /// Base URL of the homeserver, e.g. `https://matrix.org`
+        homeserver: String,
+        access_token: String,
+        /// Fully qualified Matrix ID, e.g. `@alice:matrix.org`
+        user_id: String,
+    },
+    Webhook {
+        urls: Vec<String>,
+        /// Shared secret used to sign each body with HMAC-SHA256
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secret: Option<String>,
+        #[serde(default = "default_webhook_retries")]
+        retries: u32,
+    },
+    Mqtt {
+        host: String,
+        #[serde(default = "default_mqtt_port")]
+        port: u16,
+        #[serde(default)]
+        tls: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+        /// Messages are published under `<topic_prefix>/<user_id>/`
+        #[serde(default = "default_mqtt_topic_prefix")]
+        topic_prefix: String,
+    },
+}
+
+fn default_sinks() -> Vec<SinkConfig> {
+    vec![SinkConfig::Discord {
+        token: None,
+        api_base: default_discord_api_base(),
+    }]
+}
+
+fn default_discord_api_base() -> String {
+    discord::DEFAULT_API_BASE.to_string()
+}
+
+fn default_slack_api_base() -> String {
+    slack::DEFAULT_API_BASE.to_string()
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_max_attachment_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_max_calendars_per_user() -> usize {
+    20
+}
+
+fn default_max_bytes_per_user() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    mqtt::DEFAULT_TOPIC_PREFIX.to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,19 +160,15 @@ pub struct EventMapping {
     pub emoji: Option<String>,
 }
 
-pub fn load_config() -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+pub fn load_config() -> error::Result<Config> {
     let config_content = fs::read_to_string("config.json")?;
     let config: Config = serde_json::from_str(&config_content)?;
     Ok(config)
 }
 
-pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub fn save_config(config: &Config) -> error::Result<()> {
     let config_json = serde_json::to_string_pretty(config)?;
     fs::write("config.json", config_json)?;
-
-    // Force a refresh of the status in case the new config affects it
-    tokio::spawn(connection::event_to_discord_status(config.discord.user_id, false, Arc::new(AtomicBool::new(false))));
-    
     Ok(())
 }
 
@@ -119,49 +236,149 @@ pub fn update_default_mapping(message: Option<String>, emoji: Option<String>) ->
     Ok(())
 }
 
-pub fn map_event_to_status(event_name: &str) -> Status {
-    let config = match load_config() {
-        Ok(config) => config,
-        Err(_) => {
-            // Fallback to default if config loading fails
-            return Status {
-                message: String::new(),
-                emoji: String::new(),
-            };
+/// How an event name was matched against the configured mappings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchKind {
+    Exact,
+    Partial,
+    Default,
+}
+
+/// The outcome of resolving an event name, with enough detail to explain it
+#[derive(Debug, Clone)]
+pub struct MappingMatch {
+    pub mapping: Option<EventMapping>,
+    pub kind: MatchKind,
+    pub status: Status,
+}
+
+impl MappingMatch {
+    /// Human readable reason for the match
+    pub fn explain(&self) -> String {
+        match (&self.mapping, self.kind) {
+            (Some(mapping), MatchKind::Exact) => format!("exact match on mapping `{}`", mapping.event),
+            (Some(mapping), MatchKind::Partial) => format!("event name contains mapping `{}`", mapping.event),
+            _ => "no mapping matched, using the default".to_string(),
         }
-    };
+    }
+}
 
-    // Look for exact match in mappings
-    for mapping in &config.mappings.mapping {
-        if mapping.event.to_lowercase() == event_name.to_lowercase() {
+impl Mappings {
+    /// Match an event name against the mappings: exact (case-insensitive) first, then partial, then the default
+    pub fn resolve(&self, event_name: &str) -> MappingMatch {
+        let resolve = |mapping: &EventMapping, kind: MatchKind| {
             let message = mapping.message.as_ref()
-                .unwrap_or(&config.mappings.default.message)
+                .unwrap_or(&self.default.message)
                 .clone();
             let emoji = mapping.emoji.as_ref()
-                .unwrap_or(&config.mappings.default.emoji)
+                .unwrap_or(&self.default.emoji)
                 .clone();
-            
-            return Status { message, emoji };
+
+            MappingMatch {
+                mapping: Some(mapping.clone()),
+                kind,
+                status: Status { message, emoji },
+            }
+        };
+
+        // Look for exact match in mappings
+        for mapping in &self.mapping {
+            if mapping.event.to_lowercase() == event_name.to_lowercase() {
+                return resolve(mapping, MatchKind::Exact);
+            }
+        }
+
+        // Look for partial match (if event name contains the mapping event name)
+        for mapping in &self.mapping {
+            if event_name.to_lowercase().contains(&mapping.event.to_lowercase()) {
+                return resolve(mapping, MatchKind::Partial);
+            }
+        }
+
+        // Return default if no match found
+        MappingMatch {
+            mapping: None,
+            kind: MatchKind::Default,
+            status: self.default.clone(),
         }
     }
+}
 
-    // Look for partial match (if event name contains the mapping event name)
-    for mapping in &config.mappings.mapping {
-        if event_name.to_lowercase().contains(&mapping.event.to_lowercase()) {
-            let message = mapping.message.as_ref()
-                .unwrap_or(&config.mappings.default.message)
-                .clone();
-            let emoji = mapping.emoji.as_ref()
-                .unwrap_or(&config.mappings.default.emoji)
-                .clone();
-            
-            return Status { message, emoji };
+pub fn resolve_mapping(event_name: &str) -> MappingMatch {
+    match load_config() {
+        Ok(config) => config.mappings.resolve(event_name),
+        // Fallback to default if config loading fails
+        Err(_) => MappingMatch {
+            mapping: None,
+            kind: MatchKind::Default,
+            status: Status::default(),
+        },
+    }
+}
+
+pub fn map_event_to_status(event_name: &str) -> Status {
+    resolve_mapping(event_name).status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(event: &str, message: Option<&str>, emoji: Option<&str>) -> EventMapping {
+        EventMapping {
+            event: event.to_string(),
+            message: message.map(str::to_string),
+            emoji: emoji.map(str::to_string),
         }
     }
 
-    // Return default if no match found
-    Status {
-        message: config.mappings.default.message,
-        emoji: config.mappings.default.emoji,
+    fn mappings() -> Mappings {
+        Mappings {
+            default: Status {
+                message: "Around".to_string(),
+                emoji: "💡".to_string(),
+            },
+            mapping: vec![
+                mapping("Standup", Some("In standup"), Some("🗣️")),
+                mapping("Meeting", Some("In a meeting"), None),
+                mapping("Weekly Meeting", None, Some("📅")),
+            ],
+        }
+    }
+
+    #[test]
+    fn exact_matches_ignore_case_and_win_over_partial_ones() {
+        let resolution = mappings().resolve("weekly meeting");
+        assert_eq!(resolution.kind, MatchKind::Exact);
+        assert_eq!(resolution.mapping.unwrap().event, "Weekly Meeting");
+        // Fields the mapping leaves out come from the default
+        assert_eq!(resolution.status, Status { message: "Around".to_string(), emoji: "📅".to_string() });
+    }
+
+    #[test]
+    fn partial_matches_take_the_first_contained_mapping() {
+        let resolution = mappings().resolve("Team meeting about the STANDUP");
+        assert_eq!(resolution.kind, MatchKind::Partial);
+        assert_eq!(resolution.mapping.unwrap().event, "Standup");
+        assert_eq!(resolution.status, Status { message: "In standup".to_string(), emoji: "🗣️".to_string() });
+
+        let resolution = mappings().resolve("Quarterly meeting");
+        assert_eq!(resolution.mapping.unwrap().event, "Meeting");
+        assert_eq!(resolution.status, Status { message: "In a meeting".to_string(), emoji: "💡".to_string() });
+    }
+
+    #[test]
+    fn unmatched_events_fall_back_to_the_default() {
+        let resolution = mappings().resolve("Lunch");
+        assert_eq!(resolution.kind, MatchKind::Default);
+        assert!(resolution.mapping.is_none());
+        assert_eq!(resolution.status, mappings().default);
+    }
+
+    #[test]
+    fn explains_each_kind_of_match() {
+        assert_eq!(mappings().resolve("standup").explain(), "exact match on mapping `Standup`");
+        assert_eq!(mappings().resolve("Daily standup").explain(), "event name contains mapping `Standup`");
+        assert_eq!(mappings().resolve("Lunch").explain(), "no mapping matched, using the default");
     }
 }
@@ -1,50 +1,293 @@
-use crate::calendar::get_current_event;
-use crate::config::{load_config, map_event_to_status};
-use crate::status::set_discord_status;
-use crate::util::date_perhaps_time_to_utc;
-use icalendar::Component;
+use crate::calendar::{get_current_event, get_next_event_start};
+use crate::clock::{Clock, SharedClock, SystemClock};
+use crate::config::{Config, ManualStatusPolicy, load_config, map_event_to_status};
+use crate::sink::{Sinks, build_sinks, observe_status, push_status};
+use crate::state::{ManualHold, StatusOverride, SyncPause, load_state, update_state};
+use crate::status::{ObservedStatus, Status, StatusUpdate};
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
 use tokio::time::Duration;
 
-pub async fn event_to_discord_status(discord_user_id: u64, repeat: bool, last_status_was_default: Arc<AtomicBool>) {
-    loop {
-        println!("Looping!");
-        let event = get_current_event(discord_user_id);
-        let mut sleep_duration = Duration::from_secs(60);
+/// Wakes the long-running status loop early, e.g. when an override starts or is cleared
+static WAKE: Notify = Notify::const_new();
+
+/// Make the status loop re-evaluate right away instead of waiting out its current sleep
+pub fn wake_status_loop() {
+    WAKE.notify_one();
+}
 
-        if let Some(event) = event {
-            let event_name = event.get_summary().unwrap().to_string();
-            let status = map_event_to_status(&event_name);
+/// Run a single pass of the status loop, e.g. after the config changed
+pub async fn refresh_status_now(config: &Config) {
+    event_to_discord_status(
+        config.discord.user_id,
+        false,
+        Arc::new(AtomicBool::new(false)),
+        build_sinks(config),
+        Arc::new(SystemClock),
+    )
+    .await;
+}
 
-            println!("Event: {}", event_name);
-            println!("Mapped to: {} {}", status.emoji, status.message);
+/// Like [`refresh_status_now`], but in the background
+pub fn refresh_status(config: &Config) {
+    tokio::spawn(event_to_discord_status(
+        config.discord.user_id,
+        false,
+        Arc::new(AtomicBool::new(false)),
+        build_sinks(config),
+        Arc::new(SystemClock),
+    ));
+}
 
-            let now_time = chrono::Utc::now();
-            let event_time = date_perhaps_time_to_utc(&event.get_end().unwrap());
+/// The pause currently in force, dropping it from the state once it has run out
+fn active_pause(now: DateTime<Utc>) -> Option<SyncPause> {
+    let pause = load_state().sync_pause?;
+    if pause.until.is_none_or(|until| until > now) {
+        return Some(pause);
+    }
 
-            sleep_duration = (event_time - now_time)
-                .to_std()
-                .unwrap_or(Duration::from_secs(60));
-            println!("{:?} eepy times", sleep_duration);
+    println!("Sync pause ended, resuming updates");
+    if let Err(e) = update_state(|state| state.sync_pause = None) {
+        eprintln!("Failed to save state: {e}");
+    }
+    None
+}
+
+/// The override currently in force, dropping it from the state once it has expired
+fn active_override(now: DateTime<Utc>) -> Option<StatusOverride> {
+    let status_override = load_state().status_override?;
+    if status_override.until > now {
+        return Some(status_override);
+    }
+
+    println!("Status override expired, resuming calendar updates");
+    if let Err(e) = update_state(|state| state.status_override = None) {
+        eprintln!("Failed to save state: {e}");
+    }
+    None
+}
 
-            println!("{:?}", set_discord_status(status).await);
-            last_status_was_default.store(false, Ordering::Relaxed);
+/// Whether the status currently shown was set by hand and should be left alone
+///
+/// Notices manual changes by comparing what the sinks report against what we last pushed.
+async fn respect_manual_status(
+    sinks: &Sinks,
+    policy: ManualStatusPolicy,
+    event: Option<&str>,
+    now: DateTime<Utc>,
+) -> bool {
+    let state = load_state();
+
+    if policy == ManualStatusPolicy::AlwaysOverride {
+        // A hold left over from an earlier policy would otherwise linger in the state and reports
+        if state.manual_hold.is_some()
+            && let Err(e) = update_state(|state| state.manual_hold = None)
+        {
+            eprintln!("Failed to save state: {e}");
+        }
+        return false;
+    }
+
+    if let Some(hold) = state.manual_hold {
+        let expired = hold.until.is_some_and(|until| until <= now);
+        let event_changed = policy == ManualStatusPolicy::RespectUntilNextEvent && hold.event.as_deref() != event;
+        if !expired && !event_changed {
+            println!("Respecting manual status: {} {}", hold.status.emoji, hold.status.message);
+            return true;
+        }
+
+        println!("Manual status no longer applies, resuming automatic updates");
+        if let Err(e) = update_state(|state| state.manual_hold = None) {
+            eprintln!("Failed to save state: {e}");
+        }
+        return false;
+    }
+
+    // Nothing to compare against until we've set a status ourselves
+    let Some(last_set) = state.last_set else {
+        return false;
+    };
+    let Some(observed) = observe_status(sinks).await else {
+        return false;
+    };
+    // Discord reports shortcodes we pushed as the Unicode emoji they were translated to
+    if observed.status == last_set.with_unicode_emoji() || observed.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return false;
+    }
+
+    println!(
+        "Status was changed by hand to {} {}, pausing automatic updates",
+        observed.status.emoji, observed.status.message
+    );
+    let hold = ManualHold {
+        status: observed.status,
+        until: observed.expires_at,
+        event: event.map(str::to_string),
+    };
+    if let Err(e) = update_state(|state| state.manual_hold = Some(hold)) {
+        eprintln!("Failed to save state: {e}");
+    }
+    true
+}
+
+/// Remember what the user had before the calendar took over, so it can be put back afterwards
+async fn snapshot_previous_status(sinks: &Sinks) {
+    let state = load_state();
+    if state.previous_status.is_some() {
+        return;
+    }
+
+    let Some(observed) = observe_status(sinks).await else {
+        return;
+    };
+    // An empty status has nothing worth restoring, and an event status is one of ours
+    let set_by_event = state.last_set_from_event
+        && state.last_set.as_ref().map(Status::with_unicode_emoji).as_ref() == Some(&observed.status);
+    if observed.status == Status::default() || set_by_event {
+        return;
+    }
+
+    println!("Saving previous status: {} {}", observed.status.emoji, observed.status.message);
+    if let Err(e) = update_state(|state| state.previous_status = Some(observed)) {
+        eprintln!("Failed to save state: {e}");
+    }
+}
+
+/// The status to fall back to once no events remain: the saved one if still valid, else the default
+fn status_after_events(default_status: Status, now: DateTime<Utc>) -> ObservedStatus {
+    let previous = update_state(|state| state.previous_status.take()).unwrap_or_else(|e| {
+        eprintln!("Failed to save state: {e}");
+        None
+    });
+
+    match previous {
+        Some(previous) if previous.expires_at.is_none_or(|expires_at| expires_at > now) => {
+            println!("Restoring previous status: {} {}", previous.status.emoji, previous.status.message);
+            previous
+        }
+        _ => ObservedStatus {
+            status: default_status,
+            expires_at: None,
+        },
+    }
+}
+
+/// Push an update and remember it so later manual changes can be told apart from ours
+async fn apply_status(sinks: &Sinks, update: &StatusUpdate, now: DateTime<Utc>) {
+    push_status(sinks, update).await;
+    let result = update_state(|state| {
+        state.last_set = Some(update.status.clone());
+        state.last_set_at = Some(now);
+        state.last_set_from_event = update.event.is_some();
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to save state: {e}");
+    }
+}
+
+/// Run one pass of the status loop at the clock's current time, returning how long to wait before the next
+///
+/// Like the rest of the bot, it reads `config.json`, `state.json` and `calendars/` from the working
+/// directory, so tests run it inside a temporary directory holding those files (see `Sandbox` in
+/// `tests/common`) and drive time with a [`FixedClock`](crate::clock::FixedClock).
+pub async fn tick(
+    discord_user_id: u64,
+    last_status_was_default: &AtomicBool,
+    sinks: &Sinks,
+    clock: &dyn Clock,
+) -> Duration {
+    let now = clock.now();
+    let policy = load_config()
+        .map(|config| config.manual_status_policy)
+        .unwrap_or_default();
+    let event = get_current_event(discord_user_id, now);
+    let mut sleep_duration = Duration::from_secs(60);
+
+    if let Some(pause) = active_pause(now) {
+        println!("Sync paused, leaving status alone");
+        if let Some(until) = pause.until {
+            sleep_duration = (until - now).to_std().unwrap_or(Duration::from_secs(1));
         } else {
-            println!("No current event found.");
-            // Set discord status to default
-            // Only set default status if it wasn't already set
-            if !last_status_was_default.load(Ordering::Relaxed) {
-                let default_status = load_config().unwrap().mappings.default;
-                println!("{:?}", set_discord_status(default_status).await);
-                last_status_was_default.store(true, Ordering::Relaxed);
-            }
+            // Nothing to wait for, `/resume_sync` wakes the loop
+            sleep_duration = Duration::from_secs(60 * 60);
         }
+        // Whatever is shown now wasn't set by us, so re-apply once the pause ends
+        last_status_was_default.store(false, Ordering::Relaxed);
+    } else if let Some(status_override) = active_override(now) {
+        println!(
+            "Override: {} {} until {}",
+            status_override.status.emoji, status_override.status.message, status_override.until
+        );
+        if load_state().last_set.as_ref() != Some(&status_override.status) {
+            let mut update = StatusUpdate::idle(status_override.status, now);
+            update.next_transition = Some(status_override.until);
+            apply_status(sinks, &update, now).await;
+        }
+        sleep_duration = (status_override.until - now)
+            .to_std()
+            .unwrap_or(Duration::from_secs(1));
+        last_status_was_default.store(false, Ordering::Relaxed);
+    } else if let Some(event) = event {
+        let event_name = event.summary().to_string();
+        let status = map_event_to_status(&event_name);
+
+        println!("Event: {}", event_name);
+        println!("Mapped to: {} {}", status.emoji, status.message);
+
+        // While a manual status is respected, keep checking every minute so its expiry is noticed mid-event
+        if !respect_manual_status(sinks, policy, Some(&event_name), now).await {
+            sleep_duration = (event.end - now).to_std().unwrap_or(Duration::from_secs(60));
+            println!("{:?} eepy times", sleep_duration);
+
+            snapshot_previous_status(sinks).await;
+            apply_status(sinks, &StatusUpdate::for_event(status, event_name, event.start, event.end, now), now).await;
+        }
+        last_status_was_default.store(false, Ordering::Relaxed);
+    } else {
+        println!("No current event found.");
+        // Restore the pre-event status, or the default if there was none
+        // Only do so if it wasn't already set
+        if !last_status_was_default.load(Ordering::Relaxed) && !respect_manual_status(sinks, policy, None, now).await {
+            // Without a config there's no default to apply, so try again next time round
+            let default_status = match load_config() {
+                Ok(config) => config.mappings.default,
+                Err(e) => {
+                    eprintln!("Couldn't load config for the default status: {e}");
+                    return sleep_duration;
+                }
+            };
+            let restored = status_after_events(default_status, now);
+            let mut update = StatusUpdate::idle(restored.status, now);
+            update.expires_at = restored.expires_at;
+            update.next_transition = get_next_event_start(discord_user_id, now);
+            apply_status(sinks, &update, now).await;
+            last_status_was_default.store(true, Ordering::Relaxed);
+        }
+    }
+
+    sleep_duration
+}
+
+pub async fn event_to_discord_status(
+    discord_user_id: u64,
+    repeat: bool,
+    last_status_was_default: Arc<AtomicBool>,
+    sinks: Sinks,
+    clock: SharedClock,
+) {
+    loop {
+        println!("Looping!");
+        let sleep_duration = tick(discord_user_id, &last_status_was_default, &sinks, clock.as_ref()).await;
 
         if !repeat {
             break;
         }
 
-        tokio::time::sleep(sleep_duration).await;
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => {}
+            _ = WAKE.notified() => println!("Woken early"),
+        }
     }
 }
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io;
+
+use crate::util::format_bytes;
+
+/// Errors from reading calendars and config, detailed enough to tell users what to fix
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A calendar file couldn't be parsed at all
+    CalendarParse { calendar: String, message: String },
+    /// An event lacks a property the status loop needs, so it was skipped
+    MissingProperty {
+        calendar: String,
+        property: &'static str,
+        summary: Option<String>,
+    },
+    /// An event has a property the status loop can't make sense of, so it was skipped
+    InvalidProperty {
+        calendar: String,
+        property: &'static str,
+        value: String,
+        summary: Option<String>,
+    },
+    /// A new version of a calendar has no events while the current one does, which is more
+    /// likely a glitch than intended, so the current version was kept
+    EmptyCalendar { calendar: String, previous_events: usize },
+    /// The requested version isn't in the calendar's history
+    UnknownVersion { calendar: String, version: usize },
+    /// A calendar name was rejected, e.g. because it could escape the user's directory
+    InvalidCalendarName { name: String, reason: &'static str },
+    CalendarNotFound { name: String },
+    CalendarExists { name: String },
+    /// An upload is bigger than `storage.max_attachment_bytes`
+    AttachmentTooLarge { size: u64, limit: u64 },
+    /// Saving another calendar would go over `storage.max_calendars_per_user`
+    TooManyCalendars { limit: usize },
+    /// Saving would take the user's calendars over `storage.max_bytes_per_user`
+    QuotaExceeded { used: u64, size: u64, limit: u64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Json(e) => write!(f, "JSON error: {e}"),
+            Error::CalendarParse { calendar, message } => write!(f, "Couldn't parse {calendar}: {message}"),
+            Error::MissingProperty {
+                calendar,
+                property,
+                summary: Some(summary),
+            } => write!(f, "Event `{summary}` in {calendar} has no {property}"),
+            Error::MissingProperty {
+                calendar,
+                property,
+                summary: None,
+            } => write!(f, "An event in {calendar} has no {property}"),
+            Error::InvalidProperty {
+                calendar,
+                property,
+                value,
+                summary: Some(summary),
+            } => write!(f, "Event `{summary}` in {calendar} has an invalid {property} `{value}`"),
+            Error::InvalidProperty {
+                calendar,
+                property,
+                value,
+                summary: None,
+            } => write!(f, "An event in {calendar} has an invalid {property} `{value}`"),
+            Error::EmptyCalendar {
+                calendar,
+                previous_events,
+            } => write!(
+                f,
+                "The new version of {calendar} has no events while the current one has {previous_events}, so the current one was kept"
+            ),
+            Error::UnknownVersion { calendar, version } => write!(f, "{calendar} has no version {version}"),
+            Error::InvalidCalendarName { name, reason } => write!(f, "`{name}` isn't a valid calendar name: {reason}"),
+            Error::CalendarNotFound { name } => write!(f, "Calendar file `{name}` not found"),
+            Error::CalendarExists { name } => write!(f, "A calendar with the name `{name}` already exists"),
+            Error::AttachmentTooLarge { size, limit } => write!(
+                f,
+                "The file is {}, but calendars can be at most {}",
+                format_bytes(*size),
+                format_bytes(*limit)
+            ),
+            Error::TooManyCalendars { limit } => write!(
+                f,
+                "You already have the maximum of {limit} calendars. Remove one before adding another"
+            ),
+            Error::QuotaExceeded { used, size, limit } => write!(
+                f,
+                "Your calendars use {} of your {} storage, so a {} calendar doesn't fit",
+                format_bytes(*used),
+                format_bytes(*limit),
+                format_bytes(*size)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
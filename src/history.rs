@@ -0,0 +1,354 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use icalendar::{Calendar, CalendarComponent, Component, Event};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Previous versions live in `calendars/<user_id>/history/<stem>/<timestamp>.ics`
+pub const HISTORY_DIR: &str = "history";
+
+/// How many previous versions of each calendar are kept
+pub const MAX_VERSIONS: usize = 10;
+
+/// Format of the timestamp previous versions are named after
+const VERSION_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Properties that change on every export without the event itself changing
+const VOLATILE_PROPERTIES: [&str; 4] = ["DTSTAMP", "LAST-MODIFIED", "CREATED", "SEQUENCE"];
+
+/// How the events of a replaced calendar differ from the one it replaced, by UID
+#[derive(Debug, Default)]
+pub struct CalendarDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl CalendarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Events keyed by UID, plus `RECURRENCE-ID` so overridden instances of a series count separately
+fn events_by_uid(contents: &str) -> BTreeMap<String, Event> {
+    let Ok(calendar) = contents.parse::<Calendar>() else {
+        return BTreeMap::new();
+    };
+
+    calendar
+        .components
+        .into_iter()
+        .filter_map(|component| match component {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .map(|event| {
+            // Events without a UID can only be told apart by what they are and when
+            let mut key = match event.get_uid() {
+                Some(uid) => uid.to_string(),
+                None => format!(
+                    "{}@{}",
+                    event.get_summary().unwrap_or_default(),
+                    event.property_value("DTSTART").unwrap_or_default()
+                ),
+            };
+            if let Some(recurrence_id) = event.property_value("RECURRENCE-ID") {
+                key.push_str(&format!(" ({recurrence_id})"));
+            }
+            (key, event)
+        })
+        .collect()
+}
+
+fn same_event(old: &Event, new: &Event) -> bool {
+    let stable = |event: &Event| {
+        let mut properties = event.properties().clone();
+        properties.retain(|key, _| !VOLATILE_PROPERTIES.contains(&key.as_str()));
+        properties
+    };
+    stable(old) == stable(new) && old.multi_properties() == new.multi_properties()
+}
+
+fn describe(event: &Event) -> String {
+    event.get_summary().unwrap_or("(untitled)").to_string()
+}
+
+/// Compare two versions of a calendar event by event
+pub fn diff_calendars(old: &str, new: &str) -> CalendarDiff {
+    let old_events = events_by_uid(old);
+    let new_events = events_by_uid(new);
+    let mut diff = CalendarDiff::default();
+
+    for (uid, new_event) in &new_events {
+        match old_events.get(uid) {
+            None => diff.added.push(describe(new_event)),
+            Some(old_event) if !same_event(old_event, new_event) => diff.changed.push(describe(new_event)),
+            Some(_) => {}
+        }
+    }
+    for (uid, old_event) in &old_events {
+        if !new_events.contains_key(uid) {
+            diff.removed.push(describe(old_event));
+        }
+    }
+    diff
+}
+
+/// Directory holding the previous versions of the calendar at `path`
+fn history_dir(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    path.with_file_name(HISTORY_DIR).join(stem)
+}
+
+/// Copy the current version of `path` into its history, returning where it was kept
+///
+/// Only the newest [`MAX_VERSIONS`] are kept.
+pub fn archive(path: &Path, now: DateTime<Utc>) -> Result<PathBuf> {
+    let dir = history_dir(path);
+    fs::create_dir_all(&dir)?;
+
+    // Carry on from the newest version saved within the same second, even if older ones were pruned,
+    // so a freed name isn't reused and sorted as the oldest
+    let timestamp = now.format(VERSION_FORMAT).to_string();
+    let mut suffix = calendar_versions(path)
+        .first()
+        .and_then(|newest| parse_version_stem(newest.path.file_stem()?.to_str()?))
+        .filter(|(saved_at, _)| saved_at.format(VERSION_FORMAT).to_string() == timestamp)
+        .map_or(0, |(_, suffix)| suffix + 1);
+    let name = |suffix| match suffix {
+        0 => format!("{timestamp}.ics"),
+        suffix => format!("{timestamp}-{suffix}.ics"),
+    };
+    let mut version = dir.join(name(suffix));
+    while version.exists() {
+        suffix += 1;
+        version = dir.join(name(suffix));
+    }
+
+    fs::copy(path, &version)?;
+
+    for old in calendar_versions(path).into_iter().skip(MAX_VERSIONS) {
+        if let Err(e) = fs::remove_file(&old.path) {
+            eprintln!("Failed to remove old calendar version {}: {e}", old.path.display());
+        }
+    }
+    Ok(version)
+}
+
+/// Drop the oldest versions across every calendar in `calendars_dir` until their combined size is at
+/// most `max_bytes`, returning how many were removed
+///
+/// Covers the history of removed calendars too, which would otherwise be kept forever.
+pub fn prune_history(calendars_dir: &Path, max_bytes: u64) -> usize {
+    let Ok(entries) = fs::read_dir(calendars_dir.join(HISTORY_DIR)) else {
+        return 0;
+    };
+    let mut versions: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some(calendars_dir.join(format!("{}.ics", entry.file_name().to_str()?))))
+        // Oldest first, so versions saved within the same second still go in order
+        .flat_map(|calendar| calendar_versions(&calendar).into_iter().rev())
+        .map(|version| {
+            let size = version.path.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+            (version, size)
+        })
+        .collect();
+    versions.sort_by_key(|(version, _)| version.saved_at);
+
+    let mut total: u64 = versions.iter().map(|(_, size)| size).sum();
+    let mut removed = 0;
+    for (version, size) in versions {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&version.path) {
+            Ok(()) => {
+                total -= size;
+                removed += 1;
+            }
+            Err(e) => eprintln!("Failed to remove old calendar version {}: {e}", version.path.display()),
+        }
+    }
+    removed
+}
+
+/// A previous version of a calendar
+#[derive(Debug, Clone)]
+pub struct CalendarVersion {
+    pub path: PathBuf,
+    pub saved_at: DateTime<Utc>,
+    pub events: usize,
+}
+
+/// When a version was saved, and its suffix among versions saved within the same second
+///
+/// The first version in a second has no suffix, the ones after it are named `<timestamp>-<n>`.
+fn parse_version_stem(stem: &str) -> Option<(DateTime<Utc>, u32)> {
+    let (timestamp, suffix) = match stem.split_once('-') {
+        Some((timestamp, suffix)) => (timestamp, suffix.parse().ok()?),
+        None => (stem, 0),
+    };
+    let saved_at = NaiveDateTime::parse_from_str(timestamp, VERSION_FORMAT).ok()?.and_utc();
+    Some((saved_at, suffix))
+}
+
+/// The kept versions of the calendar at `path`, newest first
+pub fn calendar_versions(path: &Path) -> Vec<CalendarVersion> {
+    let Ok(entries) = fs::read_dir(history_dir(path)) else {
+        return Vec::new();
+    };
+
+    let mut versions: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("ics"))
+        .filter_map(|path| {
+            let (saved_at, suffix) = parse_version_stem(path.file_stem()?.to_str()?)?;
+            let events = fs::read_to_string(&path)
+                .map(|contents| events_by_uid(&contents).len())
+                .unwrap_or_default();
+            Some((suffix, CalendarVersion { path, saved_at, events }))
+        })
+        .collect();
+    versions.sort_by_key(|(suffix, version)| std::cmp::Reverse((version.saved_at, *suffix)));
+    versions.into_iter().map(|(_, version)| version).collect()
+}
+
+/// Refuse to swap a calendar with events for one without, since that's usually a glitch
+fn guard_empty(path: &Path, contents: &[u8]) -> Result<()> {
+    let new_events = std::str::from_utf8(contents).map(|contents| events_by_uid(contents).len()).unwrap_or_default();
+    let previous_events = fs::read_to_string(path)
+        .map(|contents| events_by_uid(&contents).len())
+        .unwrap_or_default();
+
+    if new_events == 0 && previous_events > 0 {
+        return Err(Error::EmptyCalendar {
+            calendar: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            previous_events,
+        });
+    }
+    Ok(())
+}
+
+/// Write `contents` to `path` so readers see either the old file or the new one, never a partial write
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{file_name}.tmp"));
+    fs::write(&temp, contents)?;
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Swap in a new version of the calendar at `path`, keeping the old one in its history
+///
+/// A new version without any events is refused while the current one has some. Returns where
+/// the previous version was kept.
+pub fn replace_calendar(path: &Path, contents: &[u8]) -> Result<PathBuf> {
+    guard_empty(path, contents)?;
+    let previous = archive(path, Utc::now())?;
+    write_atomically(path, contents)?;
+    Ok(previous)
+}
+
+/// Restore the `version`th newest kept version of the calendar at `path`, counting from 1
+///
+/// The version being replaced is kept in turn, so a rollback can itself be undone. Works even
+/// if the calendar was removed since.
+pub fn rollback_calendar(path: &Path, version: usize) -> Result<CalendarVersion> {
+    let unknown = || Error::UnknownVersion {
+        calendar: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        version,
+    };
+    let restored = calendar_versions(path)
+        .into_iter()
+        .nth(version.checked_sub(1).ok_or_else(unknown)?)
+        .ok_or_else(unknown)?;
+
+    let contents = fs::read(&restored.path)?;
+    if path.exists() {
+        archive(path, Utc::now())?;
+    }
+    write_atomically(path, &contents)?;
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A calendar holding the given VEVENT bodies
+    fn calendar(events: &[&str]) -> String {
+        let mut contents = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//tests//EN\r\n");
+        for event in events {
+            contents.push_str(&format!("BEGIN:VEVENT\r\n{}\r\nEND:VEVENT\r\n", event.replace('\n', "\r\n")));
+        }
+        contents.push_str("END:VCALENDAR\r\n");
+        contents
+    }
+
+    const STANDUP: &str = "UID:standup\nSUMMARY:Standup\nDTSTART:20261019T090000Z\nDTEND:20261019T091500Z";
+    const REVIEW: &str = "UID:review\nSUMMARY:Review\nDTSTART:20261019T140000Z\nDTEND:20261019T150000Z";
+
+    #[test]
+    fn reports_added_removed_and_changed_events() {
+        let old = calendar(&[STANDUP, REVIEW]);
+        let new = calendar(&[
+            "UID:standup\nSUMMARY:Standup\nDTSTART:20261019T093000Z\nDTEND:20261019T094500Z",
+            "UID:retro\nSUMMARY:Retro\nDTSTART:20261023T160000Z\nDTEND:20261023T170000Z",
+        ]);
+
+        let diff = diff_calendars(&old, &new);
+        assert_eq!(diff.added, ["Retro"]);
+        assert_eq!(diff.removed, ["Review"]);
+        assert_eq!(diff.changed, ["Standup"]);
+        assert!(diff_calendars(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn volatile_properties_are_not_changes() {
+        let old = calendar(&[&format!("{STANDUP}\nDTSTAMP:20261001T000000Z\nSEQUENCE:0")]);
+        let new = calendar(&[&format!("{STANDUP}\nDTSTAMP:20261018T120000Z\nSEQUENCE:3\nLAST-MODIFIED:20261018T120000Z")]);
+        assert!(diff_calendars(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn overridden_instances_are_keyed_by_recurrence_id() {
+        let series = "UID:standup\nSUMMARY:Standup\nDTSTART:20261019T090000Z\nDTEND:20261019T091500Z\nRRULE:FREQ=DAILY";
+        let moved = |day: &str| {
+            format!(
+                "UID:standup\nRECURRENCE-ID:202610{day}T090000Z\nSUMMARY:Standup (moved)\n\
+                 DTSTART:202610{day}T100000Z\nDTEND:202610{day}T101500Z"
+            )
+        };
+
+        let old = calendar(&[series, &moved("20")]);
+        let new = calendar(&[series, &moved("20"), &moved("21")]);
+        let diff = diff_calendars(&old, &new);
+        assert_eq!(diff.added, ["Standup (moved)"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+
+        let diff = diff_calendars(&new, &calendar(&[series, &moved("21")]));
+        assert_eq!(diff.removed, ["Standup (moved)"]);
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn events_without_a_uid_are_keyed_by_summary_and_start() {
+        let lunch = "SUMMARY:Lunch\nDTSTART:20261019T120000Z\nDTEND:20261019T130000Z";
+        let old = calendar(&[lunch]);
+
+        // Same summary and start, so the same event with a new end
+        let longer = calendar(&["SUMMARY:Lunch\nDTSTART:20261019T120000Z\nDTEND:20261019T133000Z"]);
+        assert_eq!(diff_calendars(&old, &longer).changed, ["Lunch"]);
+
+        // A new start can't be told apart from a different event
+        let moved = diff_calendars(&old, &calendar(&["SUMMARY:Lunch\nDTSTART:20261019T123000Z\nDTEND:20261019T133000Z"]));
+        assert_eq!((moved.added, moved.removed), (vec!["Lunch".to_string()], vec!["Lunch".to_string()]));
+    }
+}
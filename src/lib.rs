@@ -1,6 +1,17 @@
 pub mod calendar;
+pub mod cli;
+pub mod clock;
 pub mod commands;
 pub mod connection;
+pub mod error;
+pub mod history;
+pub mod lint;
+pub mod notify;
+pub mod report;
+pub mod simulate;
+pub mod sink;
+pub mod state;
 pub mod status;
+pub mod store;
 pub mod config;
 pub mod util;
@@ -0,0 +1,502 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use icalendar::Calendar;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::util::{is_utc_alias, parse_ical_duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a calendar file, tied to the line it starts on
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "line {}: {}: {}", self.line, severity, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LintReport {
+    pub events: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl LintReport {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors() > 0
+    }
+
+    fn push(&mut self, line: usize, severity: Severity, message: impl Into<String>) {
+        self.issues.push(Issue {
+            line,
+            severity,
+            message: message.into(),
+        });
+    }
+}
+
+/// One unfolded content line, e.g. `DTSTART;TZID=Europe/Berlin:20250101T090000`
+#[derive(Debug)]
+struct Property {
+    line: usize,
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Join folded lines back together, remembering the line each logical line started on
+fn unfold(contents: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, raw) in contents.lines().enumerate() {
+        let raw = raw.trim_end_matches('\r');
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ => lines.push((index + 1, raw.to_string())),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: usize, text: &str) -> Option<Property> {
+    // The value starts at the first colon outside a quoted parameter value
+    let mut in_quotes = false;
+    let colon = text.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(index),
+        _ => None,
+    })?;
+
+    let (head, value) = (&text[..colon], &text[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+
+    let params = parts
+        .filter_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            Some((key.to_ascii_uppercase(), value.trim_matches('"').to_string()))
+        })
+        .collect();
+
+    Some(Property {
+        line,
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+/// Parse a DATE or DATE-TIME value, returning `None` if it is malformed
+fn parse_ical_time(value: &str, is_date: bool) -> Option<NaiveDateTime> {
+    if is_date || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0);
+    }
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()
+}
+
+const RRULE_FREQS: &[&str] = &["SECONDLY", "MINUTELY", "HOURLY", "DAILY", "WEEKLY", "MONTHLY", "YEARLY"];
+const WEEKDAYS: &[&str] = &["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+
+/// Describe what is wrong with an RRULE, if anything
+fn rrule_problem(rule: &str) -> Option<String> {
+    let mut freq = None;
+    let mut has_count = false;
+    let mut has_until = false;
+
+    for part in rule.split(';').filter(|part| !part.is_empty()) {
+        let Some((key, value)) = part.split_once('=') else {
+            return Some(format!("`{part}` is not KEY=VALUE"));
+        };
+        let numbers_ok = |allow_sign: bool| {
+            value.split(',').all(|number| {
+                let digits = if allow_sign { number.trim_start_matches(['+', '-']) } else { number };
+                !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+            })
+        };
+
+        let valid = match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(value.to_ascii_uppercase());
+                RRULE_FREQS.contains(&value.to_ascii_uppercase().as_str())
+            }
+            "COUNT" => {
+                has_count = true;
+                numbers_ok(false)
+            }
+            "INTERVAL" => numbers_ok(false),
+            "UNTIL" => {
+                has_until = true;
+                parse_ical_time(value, false).is_some()
+            }
+            "BYSECOND" | "BYMINUTE" | "BYHOUR" | "BYMONTH" => numbers_ok(false),
+            "BYMONTHDAY" | "BYYEARDAY" | "BYWEEKNO" | "BYSETPOS" => numbers_ok(true),
+            "BYDAY" => value.split(',').all(|day| {
+                let day = day.trim_start_matches(['+', '-']).trim_start_matches(|c: char| c.is_ascii_digit());
+                WEEKDAYS.contains(&day.to_ascii_uppercase().as_str())
+            }),
+            "WKST" => WEEKDAYS.contains(&value.to_ascii_uppercase().as_str()),
+            _ => return Some(format!("unknown RRULE part `{key}`")),
+        };
+        if !valid {
+            return Some(format!("invalid value `{value}` for {key}"));
+        }
+    }
+
+    if freq.is_none() {
+        return Some("missing FREQ".to_string());
+    }
+    if has_count && has_until {
+        return Some("COUNT and UNTIL can't both be set".to_string());
+    }
+    None
+}
+
+fn lint_event(report: &mut LintReport, begin_line: usize, properties: &[Property], known_tzids: &HashSet<String>) {
+    let find = |name: &str| properties.iter().find(|property| property.name == name);
+
+    let summary = find("SUMMARY").map(|summary| summary.value.trim()).filter(|summary| !summary.is_empty());
+    let label = summary.map(|summary| format!("`{summary}`")).unwrap_or_else(|| "event".to_string());
+
+    match find("SUMMARY") {
+        None => report.push(begin_line, Severity::Warning, "event has no SUMMARY, so it is skipped"),
+        Some(_) if summary.is_none() => {
+            report.push(begin_line, Severity::Warning, "event has an empty SUMMARY, so no mapping can match it")
+        }
+        Some(_) => {}
+    }
+    if find("UID").is_none() {
+        report.push(begin_line, Severity::Warning, format!("{label} has no UID"));
+    }
+
+    let mut times = Vec::new();
+    for name in ["DTSTART", "DTEND"] {
+        let Some(property) = find(name) else {
+            continue;
+        };
+
+        let is_date = property.param("VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE"));
+        match parse_ical_time(&property.value, is_date) {
+            Some(time) => times.push(time),
+            None => report.push(
+                property.line,
+                Severity::Error,
+                format!("{label} has an unparseable {name} `{}`", property.value),
+            ),
+        }
+
+        // Times are never converted between zones, so even a well-defined VTIMEZONE doesn't help
+        if let Some(tzid) = property.param("TZID")
+            && !is_utc_alias(tzid)
+        {
+            let undefined = if known_tzids.contains(tzid) {
+                ""
+            } else {
+                " (the calendar doesn't define it in a VTIMEZONE either)"
+            };
+            report.push(
+                property.line,
+                Severity::Warning,
+                format!(
+                    "{label} uses TZID `{tzid}`{undefined}; time zones aren't converted, so its {name} is read as UTC"
+                ),
+            );
+        }
+    }
+
+    if find("DTSTART").is_none() {
+        report.push(begin_line, Severity::Error, format!("{label} has no DTSTART"));
+    }
+    // Mirrors how the loader works out when an event ends
+    match (find("DTEND"), find("DURATION"), find("DTSTART")) {
+        (Some(_), _, _) => {}
+        (None, Some(duration), _) => {
+            if parse_ical_duration(&duration.value).is_none() {
+                report.push(
+                    duration.line,
+                    Severity::Error,
+                    format!("{label} has an unparseable DURATION `{}`", duration.value),
+                );
+            }
+        }
+        // All-day events without an end last the whole day
+        (None, None, Some(start))
+            if start.param("VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE")) || start.value.len() == 8 => {}
+        (None, None, _) => report.push(
+            begin_line,
+            Severity::Warning,
+            format!("{label} has neither DTEND nor DURATION, so it takes no time and is skipped"),
+        ),
+    }
+    if let [start, end] = times[..]
+        && end < start
+    {
+        let line = find("DTEND").map_or(begin_line, |property| property.line);
+        report.push(line, Severity::Error, format!("{label} ends before it starts"));
+    }
+
+    for rrule in properties.iter().filter(|property| property.name == "RRULE") {
+        if let Some(problem) = rrule_problem(&rrule.value) {
+            report.push(rrule.line, Severity::Error, format!("{label} has an unparseable RRULE: {problem}"));
+        }
+    }
+}
+
+/// Check an ICS document for problems that would stop events from being picked up correctly
+pub fn lint_calendar(contents: &str) -> LintReport {
+    let mut report = LintReport::default();
+    let lines = unfold(contents);
+
+    let properties: Vec<_> = lines
+        .iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .filter_map(|(line, text)| {
+            let property = parse_property(*line, text);
+            if property.is_none() {
+                report.push(*line, Severity::Error, format!("malformed line `{text}`"));
+            }
+            property
+        })
+        .collect();
+
+    match properties.first() {
+        Some(first) if first.name == "BEGIN" && first.value.eq_ignore_ascii_case("VCALENDAR") => {}
+        Some(first) => report.push(first.line, Severity::Error, "file doesn't start with BEGIN:VCALENDAR"),
+        None => {
+            report.push(1, Severity::Error, "file is empty");
+            return report;
+        }
+    }
+
+    // TZIDs are usually defined after the events that use them, so collect them up front
+    let mut known_tzids = HashSet::new();
+    let mut in_timezone = false;
+    for property in &properties {
+        match (property.name.as_str(), property.value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VTIMEZONE") => in_timezone = true,
+            ("END", "VTIMEZONE") => in_timezone = false,
+            ("TZID", _) if in_timezone => {
+                known_tzids.insert(property.value.clone());
+            }
+            _ => {}
+        }
+    }
+
+    let mut stack: Vec<(String, usize)> = Vec::new();
+    let mut event: Option<(usize, Vec<Property>)> = None;
+    for property in properties {
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.to_ascii_uppercase();
+                if component == "VEVENT" {
+                    event = Some((property.line, Vec::new()));
+                }
+                stack.push((component, property.line));
+            }
+            "END" => {
+                let component = property.value.to_ascii_uppercase();
+                match stack.pop() {
+                    Some((open, _)) if open == component => {}
+                    Some((open, line)) => report.push(
+                        property.line,
+                        Severity::Error,
+                        format!("END:{component} doesn't match BEGIN:{open} on line {line}"),
+                    ),
+                    None => report.push(property.line, Severity::Error, format!("END:{component} without BEGIN")),
+                }
+                if component == "VEVENT"
+                    && let Some((begin_line, event_properties)) = event.take()
+                {
+                    report.events += 1;
+                    lint_event(&mut report, begin_line, &event_properties, &known_tzids);
+                }
+            }
+            // Properties of alarms nested inside an event don't belong to the event
+            _ if stack.last().is_some_and(|(component, _)| component == "VEVENT") => {
+                if let Some((_, event_properties)) = event.as_mut() {
+                    event_properties.push(property);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (component, line) in stack {
+        report.push(line, Severity::Error, format!("BEGIN:{component} is never closed"));
+    }
+
+    if !report.has_errors()
+        && let Err(e) = contents.parse::<Calendar>()
+    {
+        report.push(1, Severity::Error, format!("calendar parser rejected the file: {e}"));
+    }
+
+    report.issues.sort_by_key(|issue| issue.line);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tzid_warnings(vtimezone: &str, tzid: &str) -> Vec<String> {
+        let contents = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//tests//EN\r\n\
+             BEGIN:VEVENT\r\nUID:a@tests\r\nDTSTAMP:20260101T000000Z\r\nSUMMARY:Standup\r\n\
+             DTSTART;TZID={tzid}:20261019T090000\r\nDTEND:20261019T093000Z\r\nEND:VEVENT\r\n\
+             {vtimezone}END:VCALENDAR\r\n"
+        );
+        lint_calendar(&contents)
+            .issues
+            .into_iter()
+            .map(|issue| issue.message)
+            .filter(|message| message.contains("TZID"))
+            .collect()
+    }
+
+    #[test]
+    fn zoned_times_are_flagged_even_with_a_vtimezone() {
+        let vtimezone = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nEND:VTIMEZONE\r\n";
+        assert_eq!(
+            tzid_warnings(vtimezone, "Europe/Berlin"),
+            ["`Standup` uses TZID `Europe/Berlin`; time zones aren't converted, so its DTSTART is read as UTC"]
+        );
+
+        let undefined = tzid_warnings("", "Europe/Berlin");
+        assert_eq!(undefined.len(), 1);
+        assert!(undefined[0].contains("doesn't define it in a VTIMEZONE"));
+    }
+
+    #[test]
+    fn utc_tzids_are_fine() {
+        assert!(tzid_warnings("", "Etc/UTC").is_empty());
+    }
+
+    /// Lint a calendar holding one event with the given properties, which start on line 5
+    fn lint_event_lines(lines: &[&str]) -> Vec<(usize, Severity, String)> {
+        let contents = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//tests//EN\r\nBEGIN:VEVENT\r\n{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            lines.join("\r\n")
+        );
+        lint_calendar(&contents)
+            .issues
+            .into_iter()
+            .map(|issue| (issue.line, issue.severity, issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn well_formed_events_are_clean() {
+        let issues = lint_event_lines(&[
+            "UID:a@tests",
+            "SUMMARY:Standup",
+            "DTSTART:20261019T090000Z",
+            "DTEND:20261019T093000Z",
+            "RRULE:FREQ=WEEKLY;BYDAY=MO,WE",
+        ]);
+        assert!(issues.is_empty(), "{issues:?}");
+    }
+
+    #[test]
+    fn a_missing_dtstart_is_an_error_on_the_begin_line() {
+        let issues = lint_event_lines(&["UID:a@tests", "SUMMARY:Standup", "DTEND:20261019T093000Z"]);
+        assert_eq!(issues, [(4, Severity::Error, "`Standup` has no DTSTART".to_string())]);
+    }
+
+    #[test]
+    fn a_missing_summary_is_skipped() {
+        let issues = lint_event_lines(&["UID:a@tests", "DTSTART:20261019T090000Z", "DTEND:20261019T093000Z"]);
+        assert_eq!(issues, [(4, Severity::Warning, "event has no SUMMARY, so it is skipped".to_string())]);
+    }
+
+    #[test]
+    fn malformed_rrules_are_reported_on_their_line() {
+        let issues = lint_event_lines(&[
+            "UID:a@tests",
+            "SUMMARY:Standup",
+            "DTSTART:20261019T090000Z",
+            "DTEND:20261019T093000Z",
+            "RRULE:FREQ=FORTNIGHTLY",
+            "RRULE:BYDAY=XX;FREQ=WEEKLY",
+            "RRULE:COUNT=3",
+        ]);
+        assert_eq!(
+            issues,
+            [
+                (9, Severity::Error, "`Standup` has an unparseable RRULE: invalid value `FORTNIGHTLY` for FREQ".to_string()),
+                (10, Severity::Error, "`Standup` has an unparseable RRULE: invalid value `XX` for BYDAY".to_string()),
+                (11, Severity::Error, "`Standup` has an unparseable RRULE: missing FREQ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn issues_point_at_the_offending_line_after_unfolding() {
+        let issues = lint_event_lines(&[
+            "UID:a@tests",
+            "SUMMARY:A long",
+            "  standup",
+            "DTSTART:20261019T090000Z",
+            "DTEND:2026-10-19",
+        ]);
+        assert_eq!(
+            issues,
+            [(9, Severity::Error, "`A long standup` has an unparseable DTEND `2026-10-19`".to_string())]
+        );
+    }
+
+    #[test]
+    fn ends_are_checked_the_way_the_loader_works_them_out() {
+        let with = |end: &str| {
+            lint_event_lines(&["UID:a@tests", "SUMMARY:Standup", "DTSTART:20261019T090000Z", end])
+                .into_iter()
+                .map(|(line, _, message)| (line, message))
+                .collect::<Vec<_>>()
+        };
+        assert!(with("DURATION:PT30M").is_empty());
+        assert_eq!(with("DURATION:30m"), [(8, "`Standup` has an unparseable DURATION `30m`".to_string())]);
+        assert_eq!(
+            with("LOCATION:Room 1"),
+            [(4, "`Standup` has neither DTEND nor DURATION, so it takes no time and is skipped".to_string())]
+        );
+
+        // All-day events without an end last the whole day
+        let all_day = lint_event_lines(&["UID:a@tests", "SUMMARY:Holiday", "DTSTART;VALUE=DATE:20261022"]);
+        assert!(all_day.is_empty(), "{all_day:?}");
+    }
+}
@@ -1,11 +1,50 @@
+use calendar2discord::cli::{
+    Command, USAGE, parse_args, run_add_mapping, run_check, run_headless, run_lint, run_set_default, run_simulate,
+    run_status,
+};
+use calendar2discord::clock::SystemClock;
 use calendar2discord::commands::start_discord_bot;
 use calendar2discord::config::load_config;
 use calendar2discord::connection::event_to_discord_status;
+use calendar2discord::sink::build_sinks;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 
 #[tokio::main]
 async fn main() {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if matches!(command, Command::Bot) {
+        return run_bot().await;
+    }
+
+    // Headless commands may not need any tokens, so a missing .env is only fatal for the bot
+    dotenvy::dotenv().ok();
+
+    let result = match command {
+        Command::Bot => Ok(()),
+        Command::Run => run_headless().await,
+        Command::Check { user_id } => run_check(user_id),
+        Command::Lint { files } => run_lint(&files),
+        Command::Simulate { from, to, user_id } => run_simulate(from, to, user_id),
+        Command::Status { user_id } => run_status(user_id),
+        Command::SetDefault { message, emoji } => run_set_default(message, emoji).await,
+        Command::AddMapping { event, message, emoji } => run_add_mapping(event, message, emoji).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run_bot() {
     dotenvy::dotenv().unwrap();
 
     let last_status_was_default = Arc::new(AtomicBool::new(false));
@@ -16,6 +55,8 @@ async fn main() {
                 config.discord.user_id,
                 true,
                 last_status_was_default.clone(),
+                build_sinks(&config),
+                Arc::new(SystemClock),
             ));
         }
         Err(e) => {
@@ -0,0 +1,14 @@
+use poise::serenity_prelude as serenity;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// DM a user through the bot account, for problems they need to act on
+pub async fn direct_message(user_id: u64, content: &str) -> Result<(), Error> {
+    let token = std::env::var("DISCORD_BOT_TOKEN")?;
+    let http = serenity::Http::new(&token);
+
+    serenity::UserId::new(user_id)
+        .direct_message(&http, serenity::CreateMessage::new().content(content))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::calendar::{CalendarEvent, get_current_events, get_next_event_start};
+use crate::config::{ManualStatusPolicy, MappingMatch, load_config, resolve_mapping};
+use crate::state::{ManualHold, StatusOverride, SyncPause, load_state};
+use crate::status::Status;
+use chrono::{DateTime, Utc};
+
+/// A current event and the mapping it resolved through
+#[derive(Debug, Clone)]
+pub struct EventResolution {
+    pub event: CalendarEvent,
+    pub resolution: MappingMatch,
+}
+
+/// What the status loop would show, and what decided it
+#[derive(Debug, Clone)]
+pub enum Resolved {
+    Paused(SyncPause),
+    Override(StatusOverride),
+    Manual(ManualHold),
+    Event(Status),
+    Restored(Status),
+    Default(Status),
+}
+
+impl Resolved {
+    /// The status that would be shown, `None` while sync is paused
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            Resolved::Paused(_) => None,
+            Resolved::Override(status_override) => Some(&status_override.status),
+            Resolved::Manual(hold) => Some(&hold.status),
+            Resolved::Event(status) | Resolved::Restored(status) | Resolved::Default(status) => Some(status),
+        }
+    }
+
+    /// Why this status won, if it wasn't simply the current event
+    pub fn reason(&self) -> Option<&'static str> {
+        match self {
+            Resolved::Paused(_) => Some("sync is paused, your status is left alone"),
+            Resolved::Override(_) => Some("override"),
+            Resolved::Manual(_) => Some("manual status respected"),
+            Resolved::Event(_) => None,
+            Resolved::Restored(_) => Some("restored from before your events"),
+            Resolved::Default(_) => Some("default"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusReport {
+    /// Every current event; only the first one sets the status
+    pub events: Vec<EventResolution>,
+    pub resolved: Resolved,
+    pub last_set: Option<(Status, DateTime<Utc>)>,
+    pub next_transition: Option<DateTime<Utc>>,
+}
+
+/// Work out the status at `now` the same way the status loop would, keeping the reasoning
+pub fn status_report(discord_user_id: u64, now: DateTime<Utc>) -> StatusReport {
+    let state = load_state();
+    let events: Vec<_> = get_current_events(discord_user_id, now)
+        .into_iter()
+        .map(|event| EventResolution {
+            resolution: resolve_mapping(event.summary()),
+            event,
+        })
+        .collect();
+
+    let pause = state.sync_pause.filter(|pause| pause.until.is_none_or(|until| until > now));
+    let status_override = state.status_override.filter(|status_override| status_override.until > now);
+    // Only a hold the status loop would still respect, see `respect_manual_status`
+    let policy = load_config().map(|config| config.manual_status_policy).unwrap_or_default();
+    let current_event = events.first().map(|current| current.event.summary());
+    let hold = state.manual_hold.filter(|hold| {
+        let expired = hold.until.is_some_and(|until| until <= now);
+        let event_changed = policy == ManualStatusPolicy::RespectUntilNextEvent && hold.event.as_deref() != current_event;
+        policy != ManualStatusPolicy::AlwaysOverride && !expired && !event_changed
+    });
+
+    let (resolved, next_transition) = if let Some(pause) = pause {
+        let until = pause.until;
+        (Resolved::Paused(pause), until)
+    } else if let Some(status_override) = status_override {
+        let until = status_override.until;
+        (Resolved::Override(status_override), Some(until))
+    } else if let Some(hold) = hold {
+        (Resolved::Manual(hold), events.first().map(|current| current.event.end))
+    } else if let Some(current) = events.first() {
+        (Resolved::Event(current.resolution.status.clone()), Some(current.event.end))
+    } else {
+        let next = get_next_event_start(discord_user_id, now);
+        match state.previous_status {
+            Some(previous) => (Resolved::Restored(previous.status), next),
+            None => {
+                let default_status = load_config().map(|config| config.mappings.default).unwrap_or_default();
+                (Resolved::Default(default_status), next)
+            }
+        }
+    };
+
+    StatusReport {
+        events,
+        resolved,
+        last_set: state.last_set.zip(state.last_set_at),
+        next_transition,
+    }
+}
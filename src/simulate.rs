@@ -0,0 +1,88 @@
+use crate::calendar::load_events;
+use crate::config::{load_config, resolve_mapping};
+use crate::status::Status;
+use chrono::{DateTime, Utc};
+
+/// A point where the status would change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub at: DateTime<Utc>,
+    /// The event driving the new status, `None` when falling back to the default
+    pub event: Option<String>,
+    pub calendar: Option<String>,
+    pub status: Status,
+}
+
+/// The outcome of [`simulate`]
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    pub transitions: Vec<Transition>,
+    /// Recurring events in the calendars; only their first occurrence is simulated
+    pub recurring: usize,
+    /// Events in the window with times in a named time zone, which are read as UTC
+    pub zoned: usize,
+}
+
+/// Walk `from..to` and list every status transition the calendars and mappings would produce
+///
+/// Uses the status loop's rules: an event counts from just after it starts until just before it ends
+/// ([`CalendarEvent::is_active_at`]), the first active event in calendar order wins, and the default
+/// applies when nothing is happening. It sees the same events the loop does, so recurring series
+/// only contribute their first occurrence and TZID times aren't converted; [`Simulation`] counts
+/// both so callers can say so. Overrides, pauses and manual statuses are runtime state and are ignored.
+/// The calendars' health is recorded as read at `now`.
+///
+/// [`CalendarEvent::is_active_at`]: crate::calendar::CalendarEvent::is_active_at
+pub fn simulate(discord_user_id: u64, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Simulation {
+    let events = load_events(discord_user_id, now);
+    let default_status = load_config().map(|config| config.mappings.default).unwrap_or_default();
+
+    // The status can only change where some event starts or ends
+    let mut boundaries: Vec<_> = events
+        .iter()
+        .flat_map(|event| [event.start, event.end])
+        .filter(|at| *at > from && *at < to)
+        .collect();
+    boundaries.push(from);
+    boundaries.sort();
+    boundaries.dedup();
+
+    let mut transitions: Vec<Transition> = Vec::new();
+    for (index, &at) in boundaries.iter().enumerate() {
+        // Nothing starts or ends before the next boundary, so any instant in between stands for the
+        // whole stretch, whereas `at` itself is where events are neither started nor ended yet
+        let until = boundaries.get(index + 1).copied().unwrap_or(to);
+        let probe = at + (until - at) / 2;
+        let active = events.iter().find(|event| event.is_active_at(probe));
+        let transition = match active {
+            Some(event) => Transition {
+                at,
+                event: Some(event.summary().to_string()),
+                calendar: Some(event.calendar.clone()),
+                status: resolve_mapping(event.summary()).status,
+            },
+            None => Transition {
+                at,
+                event: None,
+                calendar: None,
+                status: default_status.clone(),
+            },
+        };
+
+        let unchanged = transitions
+            .last()
+            .is_some_and(|last| last.event == transition.event && last.status == transition.status);
+        if !unchanged {
+            transitions.push(transition);
+        }
+    }
+
+    Simulation {
+        transitions,
+        recurring: events.iter().filter(|event| event.is_recurring()).count(),
+        zoned: events
+            .iter()
+            .filter(|event| event.start < to && event.end > from && event.has_zoned_times())
+            .count(),
+    }
+}
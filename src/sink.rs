@@ -0,0 +1,209 @@
+use crate::config::{Config, SinkConfig};
+use crate::status::{ObservedStatus, StatusUpdate};
+use async_trait::async_trait;
+use std::sync::{Arc, LazyLock, Mutex};
+
+pub mod discord;
+pub mod matrix;
+pub mod mqtt;
+pub mod slack;
+pub mod webhook;
+
+pub use discord::{DiscordClient, DiscordSink};
+pub use matrix::MatrixSink;
+pub use mqtt::MqttSink;
+pub use slack::SlackSink;
+pub use webhook::WebhookSink;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// One connection pool for every HTTP sink, since sinks get rebuilt whenever the config is saved
+pub(crate) static HTTP: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// Somewhere a resolved status can be pushed to
+#[async_trait]
+pub trait StatusSink: Send + Sync {
+    /// Short name used when logging results
+    fn name(&self) -> &str;
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error>;
+
+    /// Read back the status currently shown, for backends that support it
+    async fn observe_status(&self) -> Result<Option<ObservedStatus>, Error> {
+        Ok(None)
+    }
+}
+
+pub type Sinks = Arc<Vec<Box<dyn StatusSink>>>;
+
+/// Records every update in memory, handy for exercising the status loop without a network
+#[derive(Default, Clone)]
+pub struct MemorySink {
+    updates: Arc<Mutex<Vec<StatusUpdate>>>,
+    /// What [`StatusSink::observe_status`] reports, `None` for a sink that can't read its status back
+    shown: Option<Arc<Mutex<ObservedStatus>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink that also reports its status back, like Discord does, starting out empty
+    pub fn observable() -> Self {
+        Self {
+            updates: Default::default(),
+            shown: Some(Arc::new(Mutex::new(ObservedStatus {
+                status: Default::default(),
+                expires_at: None,
+            }))),
+        }
+    }
+
+    pub fn updates(&self) -> Vec<StatusUpdate> {
+        self.updates.lock().unwrap().clone()
+    }
+
+    /// Change the status as the user would by hand, without recording an update
+    pub fn set_by_hand(&self, observed: ObservedStatus) {
+        self.show(observed);
+    }
+
+    fn show(&self, observed: ObservedStatus) {
+        if let Some(shown) = &self.shown {
+            *shown.lock().unwrap() = observed;
+        }
+    }
+}
+
+#[async_trait]
+impl StatusSink for MemorySink {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        self.updates.lock().unwrap().push(update.clone());
+        self.show(ObservedStatus {
+            status: update.status.clone(),
+            expires_at: update.expires_at,
+        });
+        Ok(())
+    }
+
+    async fn observe_status(&self) -> Result<Option<ObservedStatus>, Error> {
+        Ok(self.shown.as_ref().map(|shown| shown.lock().unwrap().clone()))
+    }
+}
+
+pub fn build_sinks(config: &Config) -> Sinks {
+    let sinks = config
+        .sinks
+        .iter()
+        .filter_map(|sink| -> Option<Box<dyn StatusSink>> {
+            Some(match sink {
+                SinkConfig::Discord { token, api_base } => {
+                    let Some(token) = token.clone().or_else(|| std::env::var("DISCORD_USER_TOKEN").ok()) else {
+                        eprintln!("Skipping Discord sink: no token configured and DISCORD_USER_TOKEN not set");
+                        return None;
+                    };
+                    Box::new(DiscordSink::new(
+                        DiscordClient::new(token).with_api_base(api_base),
+                        Some(config.discord.user_id),
+                    ))
+                }
+                SinkConfig::Slack { token, api_base } => Box::new(SlackSink::new(token.clone(), api_base.clone())),
+                SinkConfig::Matrix {
+                    homeserver,
+                    access_token,
+                    user_id,
+                } => Box::new(MatrixSink::new(homeserver.clone(), access_token.clone(), user_id.clone())),
+                SinkConfig::Webhook { urls, secret, retries } => {
+                    Box::new(WebhookSink::new(urls.clone(), secret.clone(), *retries))
+                }
+                SinkConfig::Mqtt {
+                    host,
+                    port,
+                    tls,
+                    username,
+                    password,
+                    topic_prefix,
+                } => Box::new(MqttSink::new(
+                    host.clone(),
+                    *port,
+                    *tls,
+                    username.clone(),
+                    password.clone(),
+                    topic_prefix.clone(),
+                    config.discord.user_id.to_string(),
+                )),
+            })
+        })
+        .collect();
+    Arc::new(sinks)
+}
+
+/// Push an update to every sink, logging failures without stopping at the first one
+pub async fn push_status(sinks: &Sinks, update: &StatusUpdate) {
+    for sink in sinks.iter() {
+        match sink.set_status(update).await {
+            Ok(()) => println!("Updated {} status", sink.name()),
+            Err(e) => eprintln!("Failed to update {} status: {e}", sink.name()),
+        }
+    }
+}
+
+/// The status shown by the first sink able to report one
+pub async fn observe_status(sinks: &Sinks) -> Option<ObservedStatus> {
+    for sink in sinks.iter() {
+        match sink.observe_status().await {
+            Ok(Some(observed)) => return Some(observed),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to read {} status: {e}", sink.name()),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::Status;
+    use chrono::Utc;
+
+    struct FailingSink;
+
+    #[async_trait]
+    impl StatusSink for FailingSink {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        async fn set_status(&self, _update: &StatusUpdate) -> Result<(), Error> {
+            Err("unreachable".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn push_status_reaches_every_sink_despite_failures() {
+        let (first, second) = (MemorySink::new(), MemorySink::new());
+        let sinks: Sinks = Arc::new(vec![Box::new(first.clone()), Box::new(FailingSink), Box::new(second.clone())]);
+        let update = StatusUpdate::idle(Status {
+            message: "Focus".to_string(),
+            emoji: "🎧".to_string(),
+        }, Utc::now());
+
+        push_status(&sinks, &update).await;
+        push_status(&sinks, &update).await;
+
+        assert_eq!(first.updates().len(), 2);
+        assert_eq!(second.updates().len(), 2);
+        assert_eq!(second.updates()[0].status, update.status);
+    }
+
+    #[tokio::test]
+    async fn memory_sink_observes_nothing() {
+        let sinks: Sinks = Arc::new(vec![Box::new(MemorySink::new())]);
+        assert!(observe_status(&sinks).await.is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use super::{Error, HTTP, StatusSink};
+use crate::notify::direct_message;
+use crate::status::{ObservedStatus, Status, StatusUpdate};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant, sleep, sleep_until};
+
+pub const DEFAULT_API_BASE: &str = "https://discord.com/api/v10";
+
+/// How many times a status update is tried before giving up on rate limits or server errors
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Whether the owner has already been told their token was rejected
+///
+/// Kept outside the sink, which is rebuilt whenever the config is saved, so saving doesn't re-arm the DM.
+static WARNED_UNAUTHORIZED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub enum DiscordError {
+    /// The user token was rejected, most likely because it was revoked
+    Unauthorized,
+    /// Still rate limited after exhausting retries
+    RateLimited { retry_after: Duration },
+    Status(StatusCode),
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for DiscordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscordError::Unauthorized => write!(f, "Discord rejected the user token (401 Unauthorized)"),
+            DiscordError::RateLimited { retry_after } => {
+                write!(f, "Rate limited by Discord, retry after {retry_after:?}")
+            }
+            DiscordError::Status(status) => write!(f, "Discord responded {status}"),
+            DiscordError::Http(e) => write!(f, "Request to Discord failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DiscordError {}
+
+impl From<reqwest::Error> for DiscordError {
+    fn from(e: reqwest::Error) -> Self {
+        DiscordError::Http(e)
+    }
+}
+
+/// Parse a header holding a (possibly fractional) number of seconds
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// How long Discord wants us to wait, from the headers or the JSON body of a 429
+async fn retry_after(res: Response) -> Duration {
+    if let Some(wait) = header_secs(res.headers(), "retry-after")
+        .or_else(|| header_secs(res.headers(), "x-ratelimit-reset-after"))
+    {
+        return wait;
+    }
+
+    res.json::<serde_json::Value>()
+        .await
+        .ok()
+        .and_then(|body| body.get("retry_after")?.as_f64())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .unwrap_or(Duration::from_secs(1))
+}
+
+#[derive(Debug, Deserialize)]
+struct UserSettings {
+    custom_status: Option<CustomStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CustomStatus {
+    text: Option<String>,
+    emoji_name: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500 << attempt.min(6))
+}
+
+/// Talks to Discord's user settings API on behalf of a user token
+pub struct DiscordClient {
+    http: reqwest::Client,
+    api_base: String,
+    token: String,
+    /// Set when a response reports the bucket as exhausted
+    blocked_until: Mutex<Option<Instant>>,
+}
+
+impl DiscordClient {
+    pub fn new(token: String) -> Self {
+        Self {
+            http: HTTP.clone(),
+            api_base: DEFAULT_API_BASE.to_string(),
+            token,
+            blocked_until: Mutex::new(None),
+        }
+    }
+
+    /// Point the client at a different API, e.g. a local mock server
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn settings_url(&self) -> String {
+        format!("{}/users/@me/settings", self.api_base)
+    }
+
+    fn remember_bucket(&self, headers: &HeaderMap) {
+        let exhausted = headers
+            .get("x-ratelimit-remaining")
+            .and_then(|remaining| remaining.to_str().ok())
+            .is_some_and(|remaining| remaining == "0");
+
+        if exhausted && let Some(reset_after) = header_secs(headers, "x-ratelimit-reset-after") {
+            *self.blocked_until.lock().unwrap() = Some(Instant::now() + reset_after);
+        }
+    }
+
+    async fn wait_for_bucket(&self) {
+        let blocked_until = self.blocked_until.lock().unwrap().take();
+        if let Some(until) = blocked_until {
+            sleep_until(until).await;
+        }
+    }
+
+    /// Fetch the custom status currently shown on the account
+    pub async fn get_custom_status(&self) -> Result<ObservedStatus, DiscordError> {
+        self.wait_for_bucket().await;
+
+        let res = self
+            .http
+            .get(self.settings_url())
+            .header("authorization", &self.token)
+            .send()
+            .await?;
+        self.remember_bucket(res.headers());
+
+        match res.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED => return Err(DiscordError::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => {
+                return Err(DiscordError::RateLimited {
+                    retry_after: retry_after(res).await,
+                });
+            }
+            status => return Err(DiscordError::Status(status)),
+        }
+
+        let custom_status = res.json::<UserSettings>().await?.custom_status;
+        Ok(match custom_status {
+            Some(custom) => ObservedStatus {
+                status: Status {
+                    message: custom.text.unwrap_or_default(),
+                    emoji: custom.emoji_name.unwrap_or_default(),
+                },
+                expires_at: custom.expires_at,
+            },
+            None => ObservedStatus {
+                status: Status::default(),
+                expires_at: None,
+            },
+        })
+    }
+
+    /// Set the custom status, clearing itself at `expires_at` if given
+    pub async fn set_custom_status(
+        &self,
+        status: &Status,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), DiscordError> {
+        let mut custom_status = serde_json::json!({
+            "text": status.message,
+            "emoji_name": status.emoji
+        });
+        if let Some(expires_at) = expires_at {
+            custom_status["expires_at"] = expires_at.to_rfc3339().into();
+        }
+        let body = serde_json::json!({ "custom_status": custom_status });
+
+        let mut attempt = 0;
+        loop {
+            self.wait_for_bucket().await;
+
+            let res = self
+                .http
+                .patch(self.settings_url())
+                .header("authorization", &self.token)
+                .json(&body)
+                .send()
+                .await;
+
+            let wait = match res {
+                Ok(res) => {
+                    self.remember_bucket(res.headers());
+                    match res.status() {
+                        status if status.is_success() => return Ok(()),
+                        StatusCode::UNAUTHORIZED => return Err(DiscordError::Unauthorized),
+                        StatusCode::TOO_MANY_REQUESTS => {
+                            let wait = retry_after(res).await;
+                            if attempt + 1 >= MAX_ATTEMPTS {
+                                return Err(DiscordError::RateLimited { retry_after: wait });
+                            }
+                            wait
+                        }
+                        status if status.is_server_error() && attempt + 1 < MAX_ATTEMPTS => backoff(attempt),
+                        status => return Err(DiscordError::Status(status)),
+                    }
+                }
+                Err(e) if attempt + 1 < MAX_ATTEMPTS => {
+                    eprintln!("Discord request failed: {e}");
+                    backoff(attempt)
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            println!("Retrying Discord status update in {wait:?}");
+            sleep(wait).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Sets the user's Discord custom status
+pub struct DiscordSink {
+    client: DiscordClient,
+    /// User to DM when the token stops working
+    owner_id: Option<u64>,
+}
+
+impl DiscordSink {
+    pub fn new(client: DiscordClient, owner_id: Option<u64>) -> Self {
+        Self { client, owner_id }
+    }
+}
+
+#[async_trait]
+impl StatusSink for DiscordSink {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        let status = update.status.with_unicode_emoji();
+        if status.emoji.is_empty() && !update.status.emoji.is_empty() {
+            eprintln!(
+                "No Unicode emoji known for {}, setting the Discord status without an emoji",
+                update.status.emoji
+            );
+        }
+
+        match self.client.set_custom_status(&status, update.expires_at).await {
+            Ok(()) => {
+                WARNED_UNAUTHORIZED.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(DiscordError::Unauthorized) => {
+                // Only nag once per outage rather than on every loop iteration
+                if let Some(owner_id) = self.owner_id
+                    && !WARNED_UNAUTHORIZED.swap(true, Ordering::Relaxed)
+                    && let Err(e) = direct_message(
+                        owner_id,
+                        "⚠️ Discord rejected your user token, so your status can't be updated. \
+                         Please check `DISCORD_USER_TOKEN` or the token in your config.",
+                    )
+                    .await
+                {
+                    eprintln!("Failed to notify user about revoked token: {e}");
+                }
+                Err(DiscordError::Unauthorized.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn observe_status(&self) -> Result<Option<ObservedStatus>, Error> {
+        Ok(Some(self.client.get_custom_status().await?))
+    }
+}
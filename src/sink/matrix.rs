@@ -0,0 +1,90 @@
+use super::{Error, HTTP, StatusSink};
+use crate::status::StatusUpdate;
+use async_trait::async_trait;
+use reqwest::Url;
+
+/// Sets presence and `status_msg` through the Matrix client-server API
+pub struct MatrixSink {
+    client: reqwest::Client,
+    homeserver: String,
+    access_token: String,
+    user_id: String,
+}
+
+impl MatrixSink {
+    pub fn new(homeserver: String, access_token: String, user_id: String) -> Self {
+        Self {
+            client: HTTP.clone(),
+            homeserver,
+            access_token,
+            user_id,
+        }
+    }
+
+    fn presence_url(&self) -> Result<Url, Error> {
+        let mut url = Url::parse(&self.homeserver)?;
+        if url.cannot_be_a_base() {
+            return Err(format!("Invalid homeserver URL: {}", self.homeserver).into());
+        }
+        let path = format!(
+            "{}/_matrix/client/v3/presence/{}/status",
+            url.path().trim_end_matches('/'),
+            encode_path_segment(&self.user_id)
+        );
+        url.set_path(&path);
+        Ok(url)
+    }
+}
+
+/// Percent-encode everything but unreserved characters, as the spec asks for path parameters
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl StatusSink for MatrixSink {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        let status = update.status.with_unicode_emoji();
+        if status.emoji.is_empty() && !update.status.emoji.is_empty() {
+            eprintln!(
+                "No Unicode emoji known for {}, setting the Matrix status without an emoji",
+                update.status.emoji
+            );
+        }
+
+        // Matrix has no separate emoji field, so fold it into the message
+        let status_msg = [status.emoji.as_str(), status.message.as_str()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let presence = if update.event.is_some() { "unavailable" } else { "online" };
+
+        let body = serde_json::json!({
+            "presence": presence,
+            "status_msg": status_msg
+        });
+
+        self.client
+            .put(self.presence_url()?)
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use super::{Error, StatusSink};
+use crate::status::StatusUpdate;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, Transport};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::{Duration, timeout};
+
+pub const DEFAULT_TOPIC_PREFIX: &str = "calendar2discord";
+
+/// Distinguishes connections made by this process
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+/// Publishes retained status and next transition messages to an MQTT broker
+pub struct MqttSink {
+    host: String,
+    port: u16,
+    tls: bool,
+    username: Option<String>,
+    password: Option<String>,
+    topic_prefix: String,
+    user: String,
+}
+
+impl MqttSink {
+    pub fn new(
+        host: String,
+        port: u16,
+        tls: bool,
+        username: Option<String>,
+        password: Option<String>,
+        topic_prefix: String,
+        user: String,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            tls,
+            username,
+            password,
+            topic_prefix,
+            user,
+        }
+    }
+
+    fn topic(&self, leaf: &str) -> String {
+        format!("{}/{}/{}", self.topic_prefix.trim_end_matches('/'), self.user, leaf)
+    }
+
+    fn options(&self) -> MqttOptions {
+        // Brokers drop an existing session when another connects with its id, and a one-off refresh can
+        // publish alongside the status loop, so every connection gets an id of its own
+        let connection = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+        let client_id = format!("calendar2discord-{}-{}-{connection}", self.user, std::process::id());
+        let mut options = MqttOptions::new(client_id, &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+        if self.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+        options
+    }
+}
+
+#[async_trait]
+impl StatusSink for MqttSink {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        let status = serde_json::to_vec(&update.to_json())?;
+        // An empty retained payload clears the topic when nothing is scheduled
+        let next_transition = update.next_transition.map(|next| next.to_rfc3339()).unwrap_or_default();
+
+        // Status changes are rare, so connect per update rather than keeping a session alive
+        let (client, mut eventloop) = AsyncClient::new(self.options(), 10);
+        client.publish(self.topic("status"), QoS::AtLeastOnce, true, status).await?;
+        client
+            .publish(self.topic("next_transition"), QoS::AtLeastOnce, true, next_transition)
+            .await?;
+
+        let mut pending_acks = 2;
+        while pending_acks > 0 {
+            if let Event::Incoming(Packet::PubAck(_)) = timeout(Duration::from_secs(10), eventloop.poll()).await?? {
+                pending_acks -= 1;
+            }
+        }
+
+        client.disconnect().await?;
+        // Drive the event loop once more so the DISCONNECT packet is actually sent
+        let _ = timeout(Duration::from_secs(1), eventloop.poll()).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use super::{Error, HTTP, StatusSink};
+use crate::status::StatusUpdate;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+pub const DEFAULT_API_BASE: &str = "https://slack.com/api";
+
+/// Unicode emoji and the Slack shortcode they correspond to
+const EMOJI_SHORTCODES: &[(&str, &str)] = &[
+    ("💡", "bulb"),
+    ("❄️", "snowflake"),
+    ("🧪", "test_tube"),
+    ("📅", "date"),
+    ("🗓️", "spiral_calendar_pad"),
+    ("📆", "calendar"),
+    ("💬", "speech_balloon"),
+    ("🗣️", "speaking_head_in_silhouette"),
+    ("📞", "telephone_receiver"),
+    ("🎧", "headphones"),
+    ("💻", "computer"),
+    ("🖥️", "desktop_computer"),
+    ("🏠", "house"),
+    ("🏢", "office"),
+    ("🚗", "car"),
+    ("🚌", "bus"),
+    ("🚆", "train"),
+    ("✈️", "airplane"),
+    ("🌴", "palm_tree"),
+    ("🏖️", "beach_with_umbrella"),
+    ("🤒", "face_with_thermometer"),
+    ("🤧", "sneezing_face"),
+    ("😴", "sleeping"),
+    ("🍔", "hamburger"),
+    ("🍕", "pizza"),
+    ("☕", "coffee"),
+    ("🍽️", "knife_fork_plate"),
+    ("📚", "books"),
+    ("📝", "memo"),
+    ("✏️", "pencil2"),
+    ("🎓", "mortar_board"),
+    ("🏋️", "weight_lifter"),
+    ("🏃", "runner"),
+    ("🎮", "video_game"),
+    ("🎉", "tada"),
+    ("🔴", "red_circle"),
+    ("🟢", "large_green_circle"),
+    ("⛔", "no_entry"),
+    ("🚫", "no_entry_sign"),
+    ("🔕", "no_bell"),
+    ("⏰", "alarm_clock"),
+    ("⏳", "hourglass_flowing_sand"),
+    ("🤝", "handshake"),
+    ("👀", "eyes"),
+    ("🔥", "fire"),
+    ("✅", "white_check_mark"),
+    ("❌", "x"),
+    ("⚠️", "warning"),
+];
+
+/// Drop emoji presentation selectors so "❄" and "❄️" compare equal
+fn strip_variation_selectors(emoji: &str) -> String {
+    emoji.chars().filter(|c| *c != '\u{FE0F}' && *c != '\u{FE0E}').collect()
+}
+
+/// Translate a Unicode emoji into a Slack `:shortcode:`, passing shortcodes through untouched
+///
+/// Returns `None` for emoji Slack has no name for in [`EMOJI_SHORTCODES`].
+pub fn unicode_to_shortcode(emoji: &str) -> Option<String> {
+    if emoji.is_empty() || (emoji.len() > 2 && emoji.starts_with(':') && emoji.ends_with(':')) {
+        return Some(emoji.to_string());
+    }
+
+    let wanted = strip_variation_selectors(emoji);
+    EMOJI_SHORTCODES
+        .iter()
+        .find(|(unicode, _)| strip_variation_selectors(unicode) == wanted)
+        .map(|(_, name)| format!(":{name}:"))
+}
+
+/// Translate a Slack `:shortcode:` into its Unicode emoji, passing anything else through untouched
+///
+/// Returns `None` for shortcodes with no entry in [`EMOJI_SHORTCODES`], e.g. custom Slack emoji.
+pub fn shortcode_to_unicode(emoji: &str) -> Option<String> {
+    let Some(name) = emoji.strip_prefix(':').and_then(|name| name.strip_suffix(':')).filter(|name| !name.is_empty())
+    else {
+        return Some(emoji.to_string());
+    };
+
+    EMOJI_SHORTCODES
+        .iter()
+        .find(|(_, shortcode)| *shortcode == name)
+        .map(|(unicode, _)| unicode.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackResponse {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Sets the Slack profile status through `users.profile.set`
+pub struct SlackSink {
+    client: reqwest::Client,
+    api_base: String,
+    token: String,
+}
+
+impl SlackSink {
+    pub fn new(token: String, api_base: String) -> Self {
+        Self {
+            client: HTTP.clone(),
+            api_base: api_base.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+}
+
+#[async_trait]
+impl StatusSink for SlackSink {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        // Slack only takes shortcodes, so an emoji it has no name for clears the status emoji
+        let status_emoji = unicode_to_shortcode(&update.status.emoji).unwrap_or_else(|| {
+            eprintln!(
+                "No Slack shortcode known for {}, setting the Slack status without an emoji",
+                update.status.emoji
+            );
+            String::new()
+        });
+        let body = serde_json::json!({
+            "profile": {
+                "status_text": update.status.message,
+                "status_emoji": status_emoji,
+                "status_expiration": update.expires_at.or(update.ends_at).map(|end| end.timestamp()).unwrap_or(0)
+            }
+        });
+
+        let res: SlackResponse = self
+            .client
+            .post(format!("{}/users.profile.set", self.api_base))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if res.ok {
+            Ok(())
+        } else {
+            Err(format!("Slack API error: {}", res.error.unwrap_or_else(|| "unknown".to_string())).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_unicode_emoji_to_shortcodes() {
+        assert_eq!(unicode_to_shortcode("💡").as_deref(), Some(":bulb:"));
+        // With or without the emoji presentation selector
+        assert_eq!(unicode_to_shortcode("❄️").as_deref(), Some(":snowflake:"));
+        assert_eq!(unicode_to_shortcode("❄").as_deref(), Some(":snowflake:"));
+    }
+
+    #[test]
+    fn passes_shortcodes_and_empty_emoji_through() {
+        assert_eq!(unicode_to_shortcode(":custom_emoji:").as_deref(), Some(":custom_emoji:"));
+        assert_eq!(unicode_to_shortcode("").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn unknown_emoji_have_no_shortcode() {
+        assert_eq!(unicode_to_shortcode("🦑"), None);
+    }
+
+    #[test]
+    fn translates_shortcodes_to_unicode_emoji() {
+        assert_eq!(shortcode_to_unicode(":calendar:").as_deref(), Some("📆"));
+        assert_eq!(shortcode_to_unicode(":snowflake:").as_deref(), Some("❄️"));
+        assert_eq!(shortcode_to_unicode(":custom_emoji:"), None);
+    }
+
+    #[test]
+    fn passes_unicode_and_empty_emoji_through() {
+        assert_eq!(shortcode_to_unicode("💡").as_deref(), Some("💡"));
+        assert_eq!(shortcode_to_unicode("").as_deref(), Some(""));
+        assert_eq!(shortcode_to_unicode(":").as_deref(), Some(":"));
+    }
+
+    #[test]
+    fn translations_round_trip() {
+        for (unicode, shortcode) in EMOJI_SHORTCODES {
+            let shortcode = format!(":{shortcode}:");
+            assert_eq!(unicode_to_shortcode(unicode).as_ref(), Some(&shortcode));
+            assert_eq!(shortcode_to_unicode(&shortcode).as_deref(), Some(*unicode));
+        }
+    }
+}
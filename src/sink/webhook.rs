@@ -0,0 +1,97 @@
+use super::{Error, HTTP, StatusSink};
+use crate::status::StatusUpdate;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::time::{Duration, sleep};
+
+pub const SIGNATURE_HEADER: &str = "X-Calendar2Discord-Signature";
+
+/// POSTs a JSON description of every status change to a set of URLs
+pub struct WebhookSink {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    secret: Option<String>,
+    retries: u32,
+}
+
+impl WebhookSink {
+    pub fn new(urls: Vec<String>, secret: Option<String>, retries: u32) -> Self {
+        Self {
+            client: HTTP.clone(),
+            urls,
+            secret,
+            retries,
+        }
+    }
+
+    /// Hex encoded HMAC-SHA256 of the body, in the `sha256=<hex>` form GitHub popularised
+    fn sign(&self, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(body);
+        let hex = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        Some(format!("sha256={hex}"))
+    }
+
+    async fn post(&self, url: &str, body: &[u8], signature: Option<&str>) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            let mut req = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.to_vec());
+            if let Some(signature) = signature {
+                req = req.header(SIGNATURE_HEADER, signature);
+            }
+
+            // Only server errors and network failures are worth retrying
+            let retryable = match req.send().await {
+                Ok(res) if res.status().is_success() => return Ok(()),
+                Ok(res) if res.status().is_server_error() => format!("{url} responded {}", res.status()),
+                Ok(res) => return Err(format!("{url} responded {}", res.status()).into()),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= self.retries {
+                return Err(format!("Giving up after {} attempts: {retryable}", attempt + 1).into());
+            }
+
+            let backoff = Duration::from_secs(1 << attempt.min(6));
+            eprintln!("Webhook delivery failed ({retryable}), retrying in {backoff:?}");
+            sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl StatusSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn set_status(&self, update: &StatusUpdate) -> Result<(), Error> {
+        let body = serde_json::to_vec(&update.to_json())?;
+        let signature = self.sign(&body);
+
+        let mut failures = Vec::new();
+        for url in &self.urls {
+            if let Err(e) = self.post(url, &body, signature.as_deref()).await {
+                failures.push(e.to_string());
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; ").into())
+        }
+    }
+}
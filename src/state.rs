@@ -0,0 +1,85 @@
+use crate::status::{ObservedStatus, Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const STATE_PATH: &str = "state.json";
+
+/// Serialises read-modify-write cycles between the status loop and commands
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runtime state that has to survive restarts, kept separate from the user-edited config
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// The status the bot most recently pushed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_set: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_set_at: Option<DateTime<Utc>>,
+    /// Whether `last_set` came from a calendar event rather than the default
+    #[serde(default)]
+    pub last_set_from_event: bool,
+    /// A status the user set by hand that automatic updates are currently respecting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual_hold: Option<ManualHold>,
+    /// Set by `/pause_sync`; while present the bot leaves every status alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_pause: Option<SyncPause>,
+    /// A status pinned with `/override_status`, taking precedence over calendar events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_override: Option<StatusOverride>,
+    /// What the user had before the calendar took over, restored once events run out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<ObservedStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualHold {
+    pub status: Status,
+    /// When the manual status expires on its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Event that was active when the manual change was noticed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPause {
+    pub since: DateTime<Utc>,
+    /// `None` pauses until `/resume_sync`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusOverride {
+    pub status: Status,
+    pub until: DateTime<Utc>,
+}
+
+/// Load the saved state, starting fresh if there is none or it can't be read
+pub fn load_state() -> State {
+    fs::read_to_string(STATE_PATH)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &State) -> Result<(), Error> {
+    let state_json = serde_json::to_string_pretty(state)?;
+    fs::write(STATE_PATH, state_json)?;
+    Ok(())
+}
+
+/// Apply a change to the saved state and persist it
+pub fn update_state<T>(change: impl FnOnce(&mut State) -> T) -> Result<T, Error> {
+    let _guard = STATE_LOCK.lock().unwrap();
+    let mut state = load_state();
+    let result = change(&mut state);
+    save_state(&state)?;
+    Ok(result)
+}
@@ -1,36 +1,101 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::sink::slack::shortcode_to_unicode;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Status {
     pub message: String,
     pub emoji: String,
 }
 
-pub async fn set_discord_status(status: Status) -> Result<(), reqwest::Error> {
-    let client = reqwest::Client::new();
+impl Status {
+    /// The status with a Slack `:shortcode:` emoji swapped for its Unicode emoji, which is what Discord
+    /// and Matrix show; shortcodes with no known emoji are dropped
+    pub fn with_unicode_emoji(&self) -> Status {
+        Status {
+            message: self.message.clone(),
+            emoji: shortcode_to_unicode(&self.emoji).unwrap_or_default(),
+        }
+    }
+}
+
+/// A status as currently shown by a backend, which may have been set by hand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObservedStatus {
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A resolved status together with the calendar context it was resolved from
+#[derive(Debug, Clone)]
+pub struct StatusUpdate {
+    pub status: Status,
+    /// Summary of the event driving the status, `None` when falling back to the default
+    pub event: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// When the status is next expected to change
+    pub next_transition: Option<DateTime<Utc>>,
+    /// When the status lapses on its own, for a restored status that had an expiry
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the update was resolved, by the status loop's clock
+    pub sent_at: DateTime<Utc>,
+}
+
+impl StatusUpdate {
+    pub fn idle(status: Status, sent_at: DateTime<Utc>) -> Self {
+        Self {
+            status,
+            event: None,
+            starts_at: None,
+            ends_at: None,
+            next_transition: None,
+            expires_at: None,
+            sent_at,
+        }
+    }
 
-    let body = serde_json::json!({
-        "custom_status": {
-            "text": status.message,
-            "emoji_name": status.emoji
+    pub fn for_event(
+        status: Status,
+        event: String,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        sent_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            status,
+            event: Some(event),
+            starts_at: Some(starts_at),
+            ends_at: Some(ends_at),
+            next_transition: Some(ends_at),
+            expires_at: None,
+            sent_at,
         }
-    })
-    .to_string();
-
-    let res = client
-        .patch("https://discord.com/api/v10/users/@me/settings")
-        .header(
-            "authorization",
-            std::env::var("DISCORD_USER_TOKEN").expect("DISCORD_USER_TOKEN environment variable not set"),
-        )
-        .header("Content-Type", "application/json")
-        .body(body)
-        .send()
-        .await?;
-
-    if res.status().is_success() {
-        Ok(())
-    } else {
-        Err(reqwest::Error::from(res.error_for_status().unwrap_err()))
+    }
+
+    pub fn presence(&self) -> &'static str {
+        if self.event.is_some() { "busy" } else { "available" }
+    }
+
+    /// JSON document describing the update, shared by the webhook and MQTT sinks
+    pub fn to_json(&self) -> serde_json::Value {
+        let event = self.event.as_ref().map(|summary| {
+            serde_json::json!({
+                "summary": summary,
+                "start": self.starts_at.map(|start| start.to_rfc3339()),
+                "end": self.ends_at.map(|end| end.to_rfc3339())
+            })
+        });
+
+        serde_json::json!({
+            "status": self.status,
+            "event": event,
+            "presence": self.presence(),
+            "next_transition": self.next_transition.map(|next| next.to_rfc3339()),
+            "expires_at": self.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            "sent_at": self.sent_at.to_rfc3339()
+        })
     }
 }
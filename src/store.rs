@@ -0,0 +1,527 @@
+use chrono::Utc;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::calendar::remove_metadata;
+use crate::config::{StorageLimits, load_config};
+use crate::error::{Error, Result};
+use crate::history::{self, CalendarVersion, write_atomically};
+
+/// Directory every user's calendars live under
+pub(crate) const CALENDARS_DIR: &str = "calendars";
+
+/// Longest accepted calendar filename, including the extension
+const MAX_NAME_LEN: usize = 100;
+
+/// A user's calendar files
+///
+/// Every path handed out is checked to stay inside the user's own directory, so commands should
+/// go through here rather than building paths from user input.
+#[derive(Debug, Clone)]
+pub struct CalendarStore {
+    user_id: u64,
+    dir: PathBuf,
+    limits: StorageLimits,
+}
+
+/// How much a user is storing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageUsage {
+    pub calendars: usize,
+    /// Combined size of the current calendars, which is what the quota applies to
+    pub bytes: u64,
+    /// Combined size of the kept previous versions
+    pub history_bytes: u64,
+}
+
+impl CalendarStore {
+    /// The user's store, with the limits from the config
+    pub fn for_user(discord_user_id: u64) -> Self {
+        let limits = load_config().map(|config| config.storage).unwrap_or_default();
+        Self::with_root(CALENDARS_DIR, discord_user_id).with_limits(limits)
+    }
+
+    pub fn with_root(root: impl AsRef<Path>, discord_user_id: u64) -> Self {
+        Self {
+            user_id: discord_user_id,
+            dir: root.as_ref().join(discord_user_id.to_string()),
+            limits: StorageLimits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: StorageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The user's directory, which also holds the settings for their calendars
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn limits(&self) -> StorageLimits {
+        self.limits
+    }
+
+    /// Every user that has a calendar directory, with what they're storing
+    pub fn all_usage() -> Vec<(u64, StorageUsage)> {
+        let Ok(entries) = fs::read_dir(CALENDARS_DIR) else {
+            return Vec::new();
+        };
+        let mut usage: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .map(|user_id| (user_id, Self::with_root(CALENDARS_DIR, user_id).usage()))
+            .collect();
+        usage.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.bytes + usage.history_bytes));
+        usage
+    }
+
+    /// What this user is storing
+    pub fn usage(&self) -> StorageUsage {
+        let calendars = self.list();
+        StorageUsage {
+            calendars: calendars.len(),
+            bytes: calendars.iter().map(|path| file_size(path)).sum(),
+            history_bytes: dir_size(&self.dir.join(history::HISTORY_DIR)),
+        }
+    }
+
+    /// Refuse an upload before downloading it if it's over the attachment limit
+    pub fn check_attachment_size(&self, size: u64) -> Result<()> {
+        if size > self.limits.max_attachment_bytes {
+            return Err(Error::AttachmentTooLarge {
+                size,
+                limit: self.limits.max_attachment_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that `contents` fits, optionally in place of the existing calendar at `replacing`
+    fn check_quota(&self, contents: &[u8], replacing: Option<&Path>) -> Result<()> {
+        let size = contents.len() as u64;
+        self.check_attachment_size(size)?;
+
+        let usage = self.usage();
+        if replacing.is_none() && usage.calendars >= self.limits.max_calendars_per_user {
+            return Err(Error::TooManyCalendars {
+                limit: self.limits.max_calendars_per_user,
+            });
+        }
+
+        let used = usage.bytes.saturating_sub(replacing.map(file_size).unwrap_or_default());
+        if used + size > self.limits.max_bytes_per_user {
+            return Err(Error::QuotaExceeded {
+                used,
+                size,
+                limit: self.limits.max_bytes_per_user,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that `name` is a plain `.ics` filename, without separators or parent directories
+    pub fn validate_name(name: &str) -> Result<&str> {
+        let invalid = |reason| Error::InvalidCalendarName {
+            name: name.to_string(),
+            reason,
+        };
+
+        let Some(stem) = name.strip_suffix(".ics") else {
+            return Err(invalid("it must end in .ics"));
+        };
+        if stem.is_empty() {
+            return Err(invalid("it needs a name before .ics"));
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(invalid("it is too long"));
+        }
+        // Without separators a name can't name a parent directory or reach into another one
+        if stem.chars().any(|c| matches!(c, '/' | '\\') || c.is_control()) {
+            return Err(invalid("it can't contain slashes or control characters"));
+        }
+        if stem.starts_with('.') {
+            return Err(invalid("it can't start with a dot"));
+        }
+        Ok(name)
+    }
+
+    /// Turn a user supplied name, or an attachment's filename, into a valid calendar filename
+    pub fn sanitize_name(input: &str) -> Option<String> {
+        let stem = input.strip_suffix(".ics").unwrap_or(input);
+        // Only the last path component of an attachment's name counts
+        let stem = stem.rsplit(['/', '\\']).next().unwrap_or_default();
+        let sanitized: String = stem
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        let name = format!("{sanitized}.ics");
+        Self::validate_name(&name).ok()?;
+        Some(name)
+    }
+
+    /// Path of the calendar called `name`, which may not exist yet
+    ///
+    /// Existing files are canonicalized, so a symlink can't lead outside the user's directory either.
+    pub fn path(&self, name: &str) -> Result<PathBuf> {
+        let path = self.dir.join(Self::validate_name(name)?);
+        if !path.exists() {
+            return Ok(path);
+        }
+
+        let dir = self.dir.canonicalize()?;
+        let canonical = path.canonicalize()?;
+        if canonical.parent() != Some(dir.as_path()) {
+            return Err(Error::InvalidCalendarName {
+                name: name.to_string(),
+                reason: "it points outside your calendar directory",
+            });
+        }
+        Ok(canonical)
+    }
+
+    /// Path of an existing calendar called `name`
+    pub fn existing_path(&self, name: &str) -> Result<PathBuf> {
+        let path = self.path(name)?;
+        if !path.is_file() {
+            return Err(Error::CalendarNotFound { name: name.to_string() });
+        }
+        Ok(path)
+    }
+
+    pub fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.path(name)?.is_file())
+    }
+
+    /// Paths of every calendar in the store, sorted by name
+    pub fn list(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| Self::validate_name(name).is_ok())
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<String> {
+        Ok(fs::read_to_string(self.existing_path(name)?)?)
+    }
+
+    /// Save a new calendar, refusing to overwrite an existing one
+    pub fn create(&self, name: &str, contents: &[u8]) -> Result<PathBuf> {
+        let path = self.path(name)?;
+        if path.exists() {
+            return Err(Error::CalendarExists { name: name.to_string() });
+        }
+        self.check_quota(contents, None)?;
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(&path, contents)?;
+        Ok(path)
+    }
+
+    /// Swap in a new version of an existing calendar, keeping the old one in its history
+    pub fn replace(&self, name: &str, contents: &[u8]) -> Result<PathBuf> {
+        let path = self.existing_path(name)?;
+        self.check_quota(contents, Some(&path))?;
+        let path = history::replace_calendar(&path, contents)?;
+        self.prune_history();
+        Ok(path)
+    }
+
+    /// Delete a calendar along with its settings; its history is kept so it can be rolled back
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let path = self.existing_path(name)?;
+        history::archive(&path, Utc::now())?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::CalendarNotFound { name: name.to_string() });
+            }
+            result => result?,
+        }
+        self.prune_history();
+        remove_metadata(&self.dir, name)
+    }
+
+    /// The kept previous versions of a calendar, newest first
+    pub fn versions(&self, name: &str) -> Result<Vec<CalendarVersion>> {
+        Ok(history::calendar_versions(&self.path(name)?))
+    }
+
+    /// Restore the `version`th newest kept version of a calendar, counting from 1
+    ///
+    /// Restoring counts like an upload, so it can't bring back more than the quota allows.
+    pub fn rollback(&self, name: &str, version: usize) -> Result<CalendarVersion> {
+        let path = self.path(name)?;
+        let restoring = version
+            .checked_sub(1)
+            .and_then(|index| history::calendar_versions(&path).into_iter().nth(index));
+        if let Some(restoring) = restoring {
+            let replacing = path.is_file().then_some(path.as_path());
+            self.check_quota(&fs::read(&restoring.path)?, replacing)?;
+        }
+
+        let restored = history::rollback_calendar(&path, version)?;
+        self.prune_history();
+        Ok(restored)
+    }
+
+    /// Keep the previous versions within the same size as the quota for current calendars
+    fn prune_history(&self) {
+        let removed = history::prune_history(&self.dir, self.limits.max_bytes_per_user);
+        if removed > 0 {
+            println!("Pruned {removed} old calendar versions for user {}", self.user_id);
+        }
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    path.metadata().map(|metadata| metadata.len()).unwrap_or_default()
+}
+
+/// Combined size of every file under `dir`
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => file_size(&entry.path()),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::{read_metadata, update_metadata};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const USER_ID: u64 = 99;
+
+    /// A fresh calendars root, removed again when dropped
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new() -> Self {
+            static NEXT: AtomicU32 = AtomicU32::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "calendar2discord-store-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join(USER_ID.to_string())).unwrap();
+            Self(path)
+        }
+
+        fn store(&self) -> CalendarStore {
+            CalendarStore::with_root(&self.0, USER_ID)
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn assert_rejected(store: &CalendarStore, name: &str) {
+        assert!(
+            matches!(store.path(name), Err(Error::InvalidCalendarName { .. })),
+            "{name} should be rejected"
+        );
+        assert!(store.read_to_string(name).is_err());
+        assert!(store.create(name, b"BEGIN:VCALENDAR").is_err());
+        assert!(store.remove(name).is_err());
+    }
+
+    #[test]
+    fn names_cannot_leave_the_user_directory() {
+        let root = TempRoot::new();
+        let store = root.store();
+        fs::write(root.0.join("x.ics"), "outside").unwrap();
+
+        for name in ["../x.ics", "..\\x.ics", "a/b.ics", "/x.ics", "..", "../.ics"] {
+            assert_rejected(&store, name);
+        }
+        assert_eq!(fs::read_to_string(root.0.join("x.ics")).unwrap(), "outside");
+        assert!(!root.0.join("a").exists());
+    }
+
+    #[test]
+    fn hidden_and_overlong_names_are_rejected() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        assert_rejected(&store, ".hidden.ics");
+        assert_rejected(&store, &format!("{}.ics", "a".repeat(MAX_NAME_LEN)));
+        assert_rejected(&store, "calendar.txt");
+        assert!(store.path(&format!("{}.ics", "a".repeat(MAX_NAME_LEN - 4))).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_user_directory_are_refused() {
+        let root = TempRoot::new();
+        let store = root.store();
+        let outside = root.0.join("secret.ics");
+        fs::write(&outside, "BEGIN:VCALENDAR").unwrap();
+        std::os::unix::fs::symlink(&outside, store.dir().join("link.ics")).unwrap();
+
+        assert_rejected(&store, "link.ics");
+        assert!(store.list().is_empty());
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "BEGIN:VCALENDAR");
+    }
+
+    #[test]
+    fn sanitized_names_stay_inside() {
+        assert_eq!(CalendarStore::sanitize_name("../../etc/work.ics").as_deref(), Some("work.ics"));
+        assert_eq!(CalendarStore::sanitize_name("..\\Team Calendar.ics").as_deref(), Some("TeamCalendar.ics"));
+        assert_eq!(CalendarStore::sanitize_name(".ics"), None);
+        assert_eq!(CalendarStore::sanitize_name("../.."), None);
+    }
+
+    fn limited(root: &TempRoot, max_bytes_per_user: u64) -> CalendarStore {
+        root.store().with_limits(StorageLimits {
+            max_bytes_per_user,
+            ..StorageLimits::default()
+        })
+    }
+
+    #[test]
+    fn history_is_capped_at_the_quota() {
+        let root = TempRoot::new();
+        let store = limited(&root, 100);
+
+        store.create("work.ics", &[b'0'; 40]).unwrap();
+        for version in 1..=5 {
+            store.replace("work.ics", &[b'0' + version; 40]).unwrap();
+        }
+        store.remove("work.ics").unwrap();
+
+        let usage = store.usage();
+        assert!(usage.history_bytes <= 100, "{} bytes of history", usage.history_bytes);
+        // The newest versions are the ones kept
+        let versions = store.versions("work.ics").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(fs::read(&versions[0].path).unwrap(), [b'5'; 40]);
+        assert_eq!(fs::read(&versions[1].path).unwrap(), [b'4'; 40]);
+    }
+
+    #[test]
+    fn rollback_respects_the_quota() {
+        let root = TempRoot::new();
+        let store = limited(&root, 100);
+
+        store.create("work.ics", &[b'a'; 60]).unwrap();
+        store.replace("work.ics", &[b'b'; 30]).unwrap();
+        store.create("home.ics", &[b'c'; 60]).unwrap();
+
+        assert!(matches!(store.rollback("work.ics", 1), Err(Error::QuotaExceeded { .. })));
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "b".repeat(30));
+
+        let store = limited(&root, 200);
+        store.rollback("work.ics", 1).unwrap();
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "a".repeat(60));
+    }
+
+    const ONE_EVENT: &str = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nSUMMARY:Standup\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    const NO_EVENTS: &str = "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn emptying_a_calendar_is_refused() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        store.create("work.ics", ONE_EVENT.as_bytes()).unwrap();
+        assert!(matches!(
+            store.replace("work.ics", NO_EVENTS.as_bytes()),
+            Err(Error::EmptyCalendar { previous_events: 1, .. })
+        ));
+        assert_eq!(store.read_to_string("work.ics").unwrap(), ONE_EVENT);
+        assert!(store.versions("work.ics").unwrap().is_empty());
+
+        // Nothing is lost when the calendar was already empty
+        store.create("empty.ics", NO_EVENTS.as_bytes()).unwrap();
+        store.replace("empty.ics", NO_EVENTS.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn rolling_back_to_a_missing_version_is_refused() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        store.create("work.ics", b"first").unwrap();
+        store.replace("work.ics", b"second").unwrap();
+
+        for version in [0, 2, usize::MAX] {
+            assert!(
+                matches!(store.rollback("work.ics", version), Err(Error::UnknownVersion { version: v, .. }) if v == version),
+                "version {version}"
+            );
+        }
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "second");
+    }
+
+    #[test]
+    fn a_rollback_can_itself_be_rolled_back() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        store.create("work.ics", b"first").unwrap();
+        store.replace("work.ics", b"second").unwrap();
+
+        store.rollback("work.ics", 1).unwrap();
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "first");
+
+        // The version the rollback replaced is now the newest one kept
+        store.rollback("work.ics", 1).unwrap();
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "second");
+        let versions = store.versions("work.ics").unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(fs::read(&versions[0].path).unwrap(), b"first");
+    }
+
+    #[test]
+    fn removing_a_calendar_forgets_its_settings_in_the_store_root() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        store.create("work.ics", b"work").unwrap();
+        store.create("home.ics", b"home").unwrap();
+        for name in ["work.ics", "home.ics"] {
+            update_metadata(store.dir(), name, |metadata| metadata.priority = 5).unwrap();
+        }
+
+        store.remove("work.ics").unwrap();
+        let metadata = read_metadata(store.dir());
+        assert_eq!(metadata.keys().collect::<Vec<_>>(), ["home.ics"]);
+        assert_eq!(metadata["home.ics"].priority, 5);
+        assert_eq!(store.list(), [store.dir().join("home.ics")]);
+    }
+
+    #[test]
+    fn plain_names_round_trip() {
+        let root = TempRoot::new();
+        let store = root.store();
+
+        store.create("work.ics", b"BEGIN:VCALENDAR").unwrap();
+        assert!(matches!(store.create("work.ics", b""), Err(Error::CalendarExists { .. })));
+        assert_eq!(store.read_to_string("work.ics").unwrap(), "BEGIN:VCALENDAR");
+        assert_eq!(store.list(), [store.dir().join("work.ics")]);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
 use icalendar::{CalendarDateTime, DatePerhapsTime};
 
 pub fn date_perhaps_time_to_utc(dpt: &DatePerhapsTime) -> DateTime<Utc> {
@@ -19,3 +19,176 @@ pub fn date_perhaps_time_to_utc(dpt: &DatePerhapsTime) -> DateTime<Utc> {
         }
     }
 }
+
+/// Whether a TZID names UTC itself, so reading its times as UTC is correct
+pub fn is_utc_alias(tzid: &str) -> bool {
+    matches!(tzid, "UTC" | "GMT" | "Etc/UTC" | "Etc/GMT" | "Z")
+}
+
+/// Parse a duration such as `45m`, `2h` or `1h30m`
+pub fn parse_duration(input: &str) -> Option<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut digits = String::new();
+
+    for c in input.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let amount: i64 = digits.parse().ok()?;
+        digits.clear();
+        let delta = match c.to_ascii_lowercase() {
+            'd' => TimeDelta::try_days(amount)?,
+            'h' => TimeDelta::try_hours(amount)?,
+            'm' => TimeDelta::try_minutes(amount)?,
+            's' => TimeDelta::try_seconds(amount)?,
+            _ => return None,
+        };
+        // Durations this long are rejected rather than allowed to overflow
+        total = total.checked_add(&delta)?;
+    }
+
+    // A trailing unitless number would be ambiguous
+    if !digits.is_empty() || total <= TimeDelta::zero() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Parse a non-negative RFC 5545 `DURATION` value such as `PT1H30M`, `P1D` or `P2W`
+pub fn parse_ical_duration(input: &str) -> Option<TimeDelta> {
+    let input = input.trim();
+    let rest = input.strip_prefix('+').unwrap_or(input).strip_prefix('P')?;
+    let mut total = TimeDelta::zero();
+    let mut digits = String::new();
+    let mut in_time = false;
+    // Units seen since the start, and since the `T` once there is one
+    let mut units = 0;
+
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if c == 'T' && digits.is_empty() && !in_time {
+            in_time = true;
+            units = 0;
+            continue;
+        }
+
+        let amount: i64 = digits.parse().ok()?;
+        digits.clear();
+        let delta = match (in_time, c) {
+            (false, 'W') => TimeDelta::try_weeks(amount)?,
+            (false, 'D') => TimeDelta::try_days(amount)?,
+            (true, 'H') => TimeDelta::try_hours(amount)?,
+            (true, 'M') => TimeDelta::try_minutes(amount)?,
+            (true, 'S') => TimeDelta::try_seconds(amount)?,
+            _ => return None,
+        };
+        total = total.checked_add(&delta)?;
+        units += 1;
+    }
+
+    if !digits.is_empty() || units == 0 {
+        return None;
+    }
+    Some(total)
+}
+
+/// Parse either a duration from now or a local clock time like `17:30`, which means the next time the clock reads that
+pub fn parse_until(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Some(duration) = parse_duration(input) {
+        return now.checked_add_signed(duration);
+    }
+
+    let time = NaiveTime::parse_from_str(input.trim(), "%H:%M").ok()?;
+    let local_now = now.with_timezone(&Local);
+    let today = local_now.date_naive().and_time(time);
+    let naive = if today > local_now.naive_local() {
+        today
+    } else {
+        today.checked_add_days(Days::new(1))?
+    };
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|until| until.with_timezone(&Utc))
+}
+
+/// Parse an RFC 3339 timestamp, or a local `YYYY-MM-DD` / `YYYY-MM-DDTHH:MM` date and time
+pub fn parse_datetime(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M"))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+/// Format a byte count for people, e.g. `512 B`, `1.5 KB` or `10.0 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("45m"), TimeDelta::try_minutes(45));
+        assert_eq!(parse_duration("1h30m"), TimeDelta::try_minutes(90));
+        assert_eq!(parse_duration(" 2D "), TimeDelta::try_days(2));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for input in ["", "0m", "30", "1x", "h", "-5m"] {
+            assert_eq!(parse_duration(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn parses_ical_durations() {
+        assert_eq!(parse_ical_duration("PT1H30M"), TimeDelta::try_minutes(90));
+        assert_eq!(parse_ical_duration("P1D"), TimeDelta::try_days(1));
+        assert_eq!(parse_ical_duration("+P2W"), TimeDelta::try_weeks(2));
+        assert_eq!(parse_ical_duration("P1DT12H"), TimeDelta::try_hours(36));
+        assert_eq!(parse_ical_duration("PT0S"), Some(TimeDelta::zero()));
+        for input in ["", "P", "PT", "1H", "-PT15M", "PT1D", "P1H", "P1DT", "PT1H30", "P99999999999999W"] {
+            assert_eq!(parse_ical_duration(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert_eq!(parse_duration("100000000000d100000000000d"), None);
+        assert_eq!(parse_duration(&format!("{}s", i64::MAX)), None);
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+        assert_eq!(parse_until("100000000000d100000000000d", now), None);
+        assert_eq!(parse_until("100000000d", now), None);
+    }
+}
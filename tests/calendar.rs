@@ -0,0 +1,157 @@
+mod common;
+
+use calendar2discord::calendar::{
+    CalendarMetadata, calendar_health, calendar_paths, get_current_event, get_events_between, load_events, read_calendar,
+    read_calendar_info, update_calendar_metadata,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use common::{Sandbox, config, ics};
+use std::path::PathBuf;
+
+const USER_ID: u64 = 55;
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+}
+
+fn fixture(name: &str) -> PathBuf {
+    // Absolute, since sandboxed tests change the working directory
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn summaries(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<String> {
+    get_events_between(USER_ID, from, to, from)
+        .iter()
+        .map(|event| event.summary().to_string())
+        .collect()
+}
+
+#[test]
+fn events_between_include_everything_overlapping_the_window() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(
+        USER_ID,
+        "week.ics",
+        &ics(&[
+            ("Late", at(21, 8), at(21, 9)),
+            ("Before", at(19, 20), at(19, 22)),
+            ("Ends at the start", at(19, 23), at(20, 0)),
+            ("Straddles the start", at(19, 23), at(20, 1)),
+            ("Inside", at(20, 9), at(20, 10)),
+            ("Straddles the end", at(20, 23), at(21, 1)),
+            ("Starts at the end", at(21, 0), at(21, 1)),
+            ("Spans the window", at(19, 0), at(22, 0)),
+        ]),
+    );
+
+    // One day, the way `/agenda` asks for it
+    assert_eq!(
+        summaries(at(20, 0), at(21, 0)),
+        ["Spans the window", "Straddles the start", "Inside", "Straddles the end"]
+    );
+    // A longer range, the way `/upcoming` asks for it from mid-morning
+    assert_eq!(
+        summaries(at(20, 9), at(23, 0)),
+        ["Spans the window", "Inside", "Straddles the end", "Starts at the end", "Late"]
+    );
+    assert!(summaries(at(23, 0), at(24, 0)).is_empty());
+}
+
+#[test]
+fn loading_records_health_at_the_given_time() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(USER_ID, "week.ics", &ics(&[("Inside", at(20, 9), at(20, 10))]));
+
+    assert_eq!(load_events(USER_ID, at(20, 8)).len(), 1);
+
+    let path = calendar_paths(USER_ID).into_iter().next().unwrap();
+    let health = calendar_health(&path).unwrap();
+    assert_eq!(health.checked_at, at(20, 8));
+    assert_eq!(health.events, 1);
+}
+
+#[test]
+fn events_without_dtend_end_after_their_duration_or_a_whole_day() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(
+        USER_ID,
+        "durations.ics",
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n\
+         BEGIN:VEVENT\r\nUID:workshop\r\nSUMMARY:Workshop\r\nDTSTART:20261020T090000Z\r\nDURATION:PT1H30M\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:holiday\r\nSUMMARY:Holiday\r\nDTSTART;VALUE=DATE:20261022\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:broken\r\nSUMMARY:Broken\r\nDTSTART:20261020T090000Z\r\nDURATION:soon\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:instant\r\nSUMMARY:Instant\r\nDTSTART:20261020T090000Z\r\nEND:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+    );
+
+    let active = |at| get_current_event(USER_ID, at).map(|event| event.summary().to_string());
+    assert_eq!(active(Utc.with_ymd_and_hms(2026, 10, 20, 10, 15, 0).unwrap()).as_deref(), Some("Workshop"));
+    assert_eq!(active(Utc.with_ymd_and_hms(2026, 10, 20, 10, 45, 0).unwrap()), None);
+    assert_eq!(active(at(22, 23)).as_deref(), Some("Holiday"));
+    assert_eq!(active(at(23, 1)), None);
+
+    let path = calendar_paths(USER_ID).into_iter().next().unwrap();
+    let parsed = read_calendar(&path).unwrap();
+    let skipped: Vec<_> = parsed.skipped.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        skipped,
+        [
+            "Event `Broken` in durations.ics has an invalid DURATION `soon`",
+            "Event `Instant` in durations.ics has no DTEND or DURATION",
+        ]
+    );
+}
+
+#[test]
+fn calendar_info_counts_events_and_reports_problems() {
+    let info = read_calendar_info(&fixture("team.ics"), CalendarMetadata::default());
+    assert_eq!(info.file, "team.ics");
+    assert_eq!(info.name.as_deref(), Some("Team"));
+    assert_eq!(info.events, 3);
+    assert_eq!(info.recurring, 1);
+    assert_eq!(info.zoned, 1);
+    assert_eq!(info.range, Some((at(19, 9), at(31, 0))));
+    assert_eq!(info.skipped, ["Event `Someday` in team.ics has no DTSTART"]);
+    assert!(info.error.is_none());
+    assert!(info.metadata.enabled);
+
+    let broken = read_calendar_info(&fixture("truncated.ics"), CalendarMetadata::default());
+    assert_eq!(broken.events, 0);
+    assert!(broken.range.is_none());
+    assert!(broken.error.is_some_and(|error| error.starts_with("Couldn't parse truncated.ics")));
+
+    let missing = read_calendar_info(&fixture("missing.ics"), CalendarMetadata::default());
+    assert!(missing.modified.is_none());
+    assert!(missing.error.is_some_and(|error| error.starts_with("I/O error")));
+}
+
+#[test]
+fn higher_priority_calendars_win_and_disabled_ones_are_ignored() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(USER_ID, "a-personal.ics", &ics(&[("Dentist", at(20, 9), at(20, 11))]));
+    sandbox.write_calendar(USER_ID, "b-work.ics", &ics(&[("Planning", at(20, 10), at(20, 12))]));
+    sandbox.write_calendar(USER_ID, "c-shared.ics", &ics(&[("Team lunch", at(20, 9), at(20, 13))]));
+
+    update_calendar_metadata(USER_ID, "b-work.ics", |metadata| {
+        metadata.priority = 10;
+        metadata.display_name = Some("Work".to_string());
+    })
+    .unwrap();
+    update_calendar_metadata(USER_ID, "c-shared.ics", |metadata| {
+        metadata.priority = 20;
+        metadata.enabled = false;
+    })
+    .unwrap();
+
+    let events = load_events(USER_ID, at(20, 8));
+    let loaded: Vec<_> = events.iter().map(|event| (event.calendar.as_str(), event.summary())).collect();
+    assert_eq!(loaded, [("Work", "Planning"), ("a-personal.ics", "Dentist")]);
+
+    let active = |at| get_current_event(USER_ID, at).map(|event| event.summary().to_string());
+    // Only the personal calendar has something on before the work event starts
+    assert_eq!(active(Utc.with_ymd_and_hms(2026, 10, 20, 9, 30, 0).unwrap()).as_deref(), Some("Dentist"));
+    // Where they overlap, the higher priority work calendar wins
+    assert_eq!(active(Utc.with_ymd_and_hms(2026, 10, 20, 10, 30, 0).unwrap()).as_deref(), Some("Planning"));
+    // The disabled calendar never shows up, even though it has the highest priority
+    assert_eq!(active(Utc.with_ymd_and_hms(2026, 10, 20, 12, 30, 0).unwrap()), None);
+}
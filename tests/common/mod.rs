@@ -0,0 +1,361 @@
+//! Shared helpers for the integration tests
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by [`MockServer`]
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: String,
+    /// Path as sent on the request line, without decoding
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Recorded {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is JSON")
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+/// A canned response
+#[derive(Debug, Clone)]
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+type Routes = HashMap<(String, String), Reply>;
+type Queued = HashMap<(String, String), VecDeque<Reply>>;
+
+/// A tiny HTTP/1.1 server that records every request and answers with canned responses
+///
+/// Unknown routes get `200 {}`. Each connection serves one request and is then closed.
+#[derive(Clone)]
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+    routes: Arc<Mutex<Routes>>,
+    queued: Arc<Mutex<Queued>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Self {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            requests: Default::default(),
+            routes: Default::default(),
+            queued: Default::default(),
+        };
+
+        let handler = server.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move { handler.serve(stream).await });
+            }
+        });
+        server
+    }
+
+    /// Answer `method path` with `status` and a JSON `body` from now on
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: serde_json::Value) {
+        let reply = Reply {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        };
+        self.routes.lock().unwrap().insert((method.to_string(), path.to_string()), reply);
+    }
+
+    /// Answer the next `method path` with `status`, `headers` and a JSON `body`, ahead of [`respond`]
+    ///
+    /// Calls queue up, so a sequence of replies can be scripted.
+    ///
+    /// [`respond`]: MockServer::respond
+    pub fn respond_once(&self, method: &str, path: &str, status: u16, headers: &[(&str, &str)], body: serde_json::Value) {
+        let reply = Reply {
+            status,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: body.to_string(),
+        };
+        self.queued
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), path.to_string()))
+            .or_default()
+            .push_back(reply);
+    }
+
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Recorded requests with the given method, oldest first
+    pub fn requests_with(&self, method: &str) -> Vec<Recorded> {
+        self.requests().into_iter().filter(|request| request.method == method).collect()
+    }
+
+    async fn serve(&self, mut stream: TcpStream) {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        let header_end = loop {
+            let read = stream.read(&mut chunk).await.unwrap_or(0);
+            if read == 0 {
+                return;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let headers: HashMap<_, _> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+
+        let length: usize = headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+        while buffer.len() < header_end + length {
+            let read = stream.read(&mut chunk).await.unwrap_or(0);
+            if read == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+        let body = String::from_utf8_lossy(&buffer[header_end..]).into_owned();
+
+        let route = (method.clone(), path.split('?').next().unwrap_or_default().to_string());
+        let queued = self.queued.lock().unwrap().get_mut(&route).and_then(VecDeque::pop_front);
+        let reply = queued
+            .or_else(|| self.routes.lock().unwrap().get(&route).cloned())
+            .unwrap_or(Reply {
+                status: 200,
+                headers: Vec::new(),
+                body: "{}".to_string(),
+            });
+
+        self.requests.lock().unwrap().push(Recorded {
+            method,
+            path,
+            headers,
+            body,
+        });
+
+        let headers: String = reply.headers.iter().map(|(name, value)| format!("{name}: {value}\r\n")).collect();
+        let response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{}",
+            reply.status,
+            reply.body.len(),
+            reply.body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+}
+
+/// A message published to [`MqttBroker`]
+#[derive(Debug, Clone)]
+pub struct Published {
+    pub client_id: String,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+/// Just enough of an MQTT 3.1.1 broker to accept connections and record what gets published
+///
+/// Stands in for a local Mosquitto so the tests don't need one installed.
+#[derive(Clone)]
+pub struct MqttBroker {
+    pub port: u16,
+    client_ids: Arc<Mutex<Vec<String>>>,
+    published: Arc<Mutex<Vec<Published>>>,
+}
+
+impl MqttBroker {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let broker = Self {
+            port: listener.local_addr().unwrap().port(),
+            client_ids: Default::default(),
+            published: Default::default(),
+        };
+
+        let handler = broker.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move { handler.serve(stream).await });
+            }
+        });
+        broker
+    }
+
+    /// Client ids in the order they connected
+    pub fn client_ids(&self) -> Vec<String> {
+        self.client_ids.lock().unwrap().clone()
+    }
+
+    pub fn published(&self) -> Vec<Published> {
+        self.published.lock().unwrap().clone()
+    }
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = stream.read_u8().await.ok()?;
+        let (mut length, mut shift) = (0usize, 0);
+        loop {
+            let byte = stream.read_u8().await.ok()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    async fn serve(&self, mut stream: TcpStream) {
+        let read_string = |bytes: &[u8], at: usize| {
+            let length = u16::from_be_bytes([bytes[at], bytes[at + 1]]) as usize;
+            (String::from_utf8_lossy(&bytes[at + 2..at + 2 + length]).into_owned(), at + 2 + length)
+        };
+        let mut client_id = String::new();
+
+        while let Some((header, body)) = Self::read_packet(&mut stream).await {
+            match header >> 4 {
+                // CONNECT: protocol name, level, flags and keep alive come before the client id
+                1 => {
+                    let (_, at) = read_string(&body, 0);
+                    client_id = read_string(&body, at + 4).0;
+                    self.client_ids.lock().unwrap().push(client_id.clone());
+                    let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await;
+                }
+                // PUBLISH
+                3 => {
+                    let qos = (header >> 1) & 0x03;
+                    let (topic, mut at) = read_string(&body, 0);
+                    let packet_id = (qos > 0).then(|| [body[at], body[at + 1]]);
+                    if packet_id.is_some() {
+                        at += 2;
+                    }
+                    self.published.lock().unwrap().push(Published {
+                        client_id: client_id.clone(),
+                        topic,
+                        payload: body[at..].to_vec(),
+                        retain: header & 0x01 == 1,
+                    });
+                    if let Some([high, low]) = packet_id {
+                        let _ = stream.write_all(&[0x40, 0x02, high, low]).await;
+                    }
+                }
+                // PINGREQ
+                12 => {
+                    let _ = stream.write_all(&[0xd0, 0x00]).await;
+                }
+                // DISCONNECT
+                14 => return,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Only one sandbox may own the working directory at a time
+static SANDBOX_LOCK: Mutex<()> = Mutex::new(());
+
+/// A throwaway working directory for code that reads `config.json`, `state.json` and `calendars/`
+///
+/// The status loop resolves those paths against the current directory, so the sandbox switches into
+/// a fresh temporary directory and holds a lock until dropped, keeping tests in the same binary
+/// from sharing files. Each test binary runs in its own process, so binaries don't interfere.
+pub struct Sandbox {
+    pub dir: std::path::PathBuf,
+    previous_dir: std::path::PathBuf,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+impl Sandbox {
+    pub fn new(config: serde_json::Value) -> Self {
+        let lock = SANDBOX_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        static NEXT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "calendar2discord-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.json"), serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+        let previous_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        Self {
+            dir,
+            previous_dir,
+            _lock: lock,
+        }
+    }
+
+    /// Save a calendar file for `user_id`
+    pub fn write_calendar(&self, user_id: u64, name: &str, contents: &str) {
+        let user_dir = self.dir.join("calendars").join(user_id.to_string());
+        std::fs::create_dir_all(&user_dir).unwrap();
+        std::fs::write(user_dir.join(name), contents).unwrap();
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous_dir);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A config for `user_id` with a default status and the given `(event, message, emoji)` mappings
+pub fn config(user_id: u64, mappings: &[(&str, &str, &str)]) -> serde_json::Value {
+    let mappings: Vec<_> = mappings
+        .iter()
+        .map(|(event, message, emoji)| serde_json::json!({ "event": event, "message": message, "emoji": emoji }))
+        .collect();
+    serde_json::json!({
+        "discord": { "token": "unused", "user_id": user_id },
+        "mappings": {
+            "default": { "message": "Around", "emoji": "💡" },
+            "mapping": mappings
+        },
+        "sinks": []
+    })
+}
+
+/// An iCalendar document with one event per `(summary, start, end)`, times in UTC
+pub fn ics(events: &[(&str, chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)]) -> String {
+    let mut ics = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//calendar2discord//tests//EN\r\n");
+    for (index, (summary, start, end)) in events.iter().enumerate() {
+        ics.push_str(&format!(
+            "BEGIN:VEVENT\r\nUID:event-{index}@tests\r\nDTSTAMP:20260101T000000Z\r\nSUMMARY:{summary}\r\nDTSTART:{}\r\nDTEND:{}\r\nEND:VEVENT\r\n",
+            start.format("%Y%m%dT%H%M%SZ"),
+            end.format("%Y%m%dT%H%M%SZ")
+        ));
+    }
+    ics.push_str("END:VCALENDAR\r\n");
+    ics
+}
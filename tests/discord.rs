@@ -0,0 +1,233 @@
+mod common;
+
+use calendar2discord::clock::{Clock, FixedClock};
+use calendar2discord::connection::tick;
+use calendar2discord::sink::{DiscordClient, DiscordSink, Sinks};
+use chrono::{TimeDelta, TimeZone, Utc};
+use common::{MockServer, Sandbox, config, ics};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+const USER_ID: u64 = 42;
+const SETTINGS: &str = "/users/@me/settings";
+
+fn discord_sinks(server: &MockServer) -> Sinks {
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+    Arc::new(vec![Box::new(DiscordSink::new(client, None))])
+}
+
+#[tokio::test]
+async fn tick_patches_the_mapped_status_and_then_the_default() {
+    let start = Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap();
+    let end = start + TimeDelta::minutes(15);
+    let sandbox = Sandbox::new(config(USER_ID, &[("Standup", "In standup", "📅")]));
+    sandbox.write_calendar(USER_ID, "work.ics", &ics(&[("Daily Standup", start, end)]));
+
+    let server = MockServer::start().await;
+    server.respond("GET", SETTINGS, 200, serde_json::json!({ "custom_status": null }));
+    let sinks = discord_sinks(&server);
+    let clock = FixedClock::new(start + TimeDelta::minutes(5));
+    let last_status_was_default = AtomicBool::new(false);
+
+    let sleep = tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    assert_eq!(sleep, (end - clock.now()).to_std().unwrap());
+
+    clock.set(end);
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+
+    let patches = server.requests_with("PATCH");
+    assert_eq!(patches.len(), 2);
+    assert!(patches.iter().all(|patch| patch.path == SETTINGS));
+    assert!(patches.iter().all(|patch| patch.header("authorization") == Some("user-token")));
+    assert_eq!(
+        patches[0].json(),
+        serde_json::json!({ "custom_status": { "text": "In standup", "emoji_name": "📅" } })
+    );
+    assert_eq!(
+        patches[1].json(),
+        serde_json::json!({ "custom_status": { "text": "Around", "emoji_name": "💡" } })
+    );
+}
+
+#[tokio::test]
+async fn tick_leaves_the_default_alone_once_set() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(USER_ID, "empty.ics", &ics(&[]));
+
+    let server = MockServer::start().await;
+    let sinks = discord_sinks(&server);
+    let clock = FixedClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap());
+    let last_status_was_default = AtomicBool::new(false);
+
+    for _ in 0..3 {
+        tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+        clock.advance(TimeDelta::minutes(1));
+    }
+
+    let patches = server.requests_with("PATCH");
+    assert_eq!(patches.len(), 1);
+    assert_eq!(patches[0].json()["custom_status"]["text"], "Around");
+}
+
+#[tokio::test]
+async fn unauthorized_tokens_surface_as_errors() {
+    let server = MockServer::start().await;
+    server.respond("PATCH", SETTINGS, 401, serde_json::json!({ "message": "401: Unauthorized" }));
+    let client = DiscordClient::new("revoked".to_string()).with_api_base(&server.url);
+
+    let status = calendar2discord::status::Status {
+        message: "Around".to_string(),
+        emoji: "💡".to_string(),
+    };
+    let error = client.set_custom_status(&status, None).await.unwrap_err();
+    assert!(matches!(error, calendar2discord::sink::discord::DiscordError::Unauthorized));
+    assert_eq!(server.requests_with("PATCH").len(), 1);
+}
+
+#[tokio::test]
+async fn shortcodes_are_sent_as_unicode_and_not_mistaken_for_manual_changes() {
+    let start = Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap();
+    let end = start + TimeDelta::minutes(30);
+    let mut config = config(USER_ID, &[("Standup", "In standup", ":calendar:")]);
+    config["manual_status_policy"] = "respect_manual".into();
+    let sandbox = Sandbox::new(config);
+    sandbox.write_calendar(USER_ID, "work.ics", &ics(&[("Standup", start, end)]));
+
+    let server = MockServer::start().await;
+    server.respond("GET", SETTINGS, 200, serde_json::json!({ "custom_status": null }));
+    let sinks = discord_sinks(&server);
+    let clock = FixedClock::new(start + TimeDelta::minutes(5));
+    let last_status_was_default = AtomicBool::new(false);
+
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    // Discord now reports what we sent, with the emoji it was translated to
+    server.respond(
+        "GET",
+        SETTINGS,
+        200,
+        serde_json::json!({ "custom_status": { "text": "In standup", "emoji_name": "📆" } }),
+    );
+    clock.set(start + TimeDelta::minutes(10));
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    clock.set(end);
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+
+    let patches = server.requests_with("PATCH");
+    assert_eq!(patches.len(), 3);
+    assert_eq!(patches[0].json()["custom_status"]["emoji_name"], "📆");
+    assert_eq!(patches[2].json()["custom_status"]["text"], "Around");
+    let state = std::fs::read_to_string(sandbox.dir.join("state.json")).unwrap();
+    assert!(!state.contains("manual_hold"), "{state}");
+}
+
+fn around() -> calendar2discord::status::Status {
+    calendar2discord::status::Status {
+        message: "Around".to_string(),
+        emoji: "💡".to_string(),
+    }
+}
+
+// Paused time skips ahead through the waits, which are measured on tokio's clock
+#[tokio::test(start_paused = true)]
+async fn rate_limited_updates_wait_out_retry_after() {
+    let server = MockServer::start().await;
+    server.respond_once("PATCH", SETTINGS, 429, &[], serde_json::json!({ "retry_after": 2.5, "global": false }));
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+
+    let started = tokio::time::Instant::now();
+    client.set_custom_status(&around(), None).await.unwrap();
+
+    assert!(started.elapsed() >= std::time::Duration::from_millis(2500), "{:?}", started.elapsed());
+    assert_eq!(server.requests_with("PATCH").len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_after_headers_take_precedence_over_the_body() {
+    let server = MockServer::start().await;
+    server.respond_once("PATCH", SETTINGS, 429, &[("Retry-After", "4")], serde_json::json!({ "retry_after": 0.1 }));
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+
+    let started = tokio::time::Instant::now();
+    client.set_custom_status(&around(), None).await.unwrap();
+
+    assert!(started.elapsed() >= std::time::Duration::from_secs(4), "{:?}", started.elapsed());
+}
+
+#[tokio::test(start_paused = true)]
+async fn an_exhausted_bucket_delays_the_next_call() {
+    let server = MockServer::start().await;
+    server.respond_once(
+        "PATCH",
+        SETTINGS,
+        200,
+        &[("X-RateLimit-Remaining", "0"), ("X-RateLimit-Reset-After", "3")],
+        serde_json::json!({}),
+    );
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+
+    client.set_custom_status(&around(), None).await.unwrap();
+    let started = tokio::time::Instant::now();
+    client.get_custom_status().await.unwrap();
+
+    assert!(started.elapsed() >= std::time::Duration::from_secs(3), "{:?}", started.elapsed());
+    // Only the one exhausted response blocks
+    let started = tokio::time::Instant::now();
+    client.set_custom_status(&around(), None).await.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(1), "{:?}", started.elapsed());
+}
+
+#[tokio::test(start_paused = true)]
+async fn persistent_server_errors_give_up_after_max_attempts() {
+    use calendar2discord::sink::discord::{DiscordError, MAX_ATTEMPTS};
+
+    let server = MockServer::start().await;
+    server.respond("PATCH", SETTINGS, 502, serde_json::json!({}));
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+
+    let error = client.set_custom_status(&around(), None).await.unwrap_err();
+
+    assert!(matches!(error, DiscordError::Status(status) if status.as_u16() == 502), "{error}");
+    assert_eq!(server.requests_with("PATCH").len(), MAX_ATTEMPTS as usize);
+}
+
+#[tokio::test(start_paused = true)]
+async fn server_errors_back_off_and_recover() {
+    let server = MockServer::start().await;
+    server.respond_once("PATCH", SETTINGS, 500, &[], serde_json::json!({}));
+    server.respond_once("PATCH", SETTINGS, 503, &[], serde_json::json!({}));
+    let client = DiscordClient::new("user-token".to_string()).with_api_base(&server.url);
+
+    let started = tokio::time::Instant::now();
+    client.set_custom_status(&around(), None).await.unwrap();
+
+    // 500ms, then 1s
+    assert!(started.elapsed() >= std::time::Duration::from_millis(1500), "{:?}", started.elapsed());
+    assert_eq!(server.requests_with("PATCH").len(), 3);
+}
+
+#[tokio::test]
+async fn restored_statuses_keep_their_expiry() {
+    let sandbox = Sandbox::new(config(USER_ID, &[]));
+    sandbox.write_calendar(USER_ID, "empty.ics", &ics(&[]));
+    let expires_at = Utc.with_ymd_and_hms(2026, 10, 18, 17, 0, 0).unwrap();
+    std::fs::write(
+        sandbox.dir.join("state.json"),
+        serde_json::json!({
+            "previous_status": { "status": { "message": "Focus", "emoji": "🎧" }, "expires_at": expires_at }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let server = MockServer::start().await;
+    let clock = FixedClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap());
+    tick(USER_ID, &AtomicBool::new(false), &discord_sinks(&server), &clock).await;
+
+    let patches = server.requests_with("PATCH");
+    assert_eq!(
+        patches[0].json(),
+        serde_json::json!({
+            "custom_status": { "text": "Focus", "emoji_name": "🎧", "expires_at": "2026-10-18T17:00:00+00:00" }
+        })
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use calendar2discord::sink::{MatrixSink, StatusSink};
+use calendar2discord::status::{Status, StatusUpdate};
+use chrono::{DateTime, TimeZone, Utc};
+use common::MockServer;
+
+fn status(message: &str, emoji: &str) -> Status {
+    Status {
+        message: message.to_string(),
+        emoji: emoji.to_string(),
+    }
+}
+
+/// When the status loop resolved the updates sent below
+fn sent_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap()
+}
+
+#[tokio::test]
+async fn puts_presence_for_the_encoded_user_id() {
+    let server = MockServer::start().await;
+    let sink = MatrixSink::new(server.url.clone(), "syt_token".to_string(), "@alice:example.org".to_string());
+
+    sink.set_status(&StatusUpdate::idle(status("Around", "💡"), sent_at())).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].path, "/_matrix/client/v3/presence/%40alice%3Aexample.org/status");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer syt_token"));
+    assert_eq!(
+        requests[0].json(),
+        serde_json::json!({ "presence": "online", "status_msg": "💡 Around" })
+    );
+}
+
+#[tokio::test]
+async fn keeps_the_homeserver_path_prefix() {
+    let server = MockServer::start().await;
+    let sink = MatrixSink::new(
+        format!("{}/matrix/", server.url),
+        "syt_token".to_string(),
+        "@bob/evil?x#y:example.org".to_string(),
+    );
+
+    sink.set_status(&StatusUpdate::idle(status("Around", ""), sent_at())).await.unwrap();
+
+    // Characters that would change the path stay inside the one segment
+    assert_eq!(
+        server.requests()[0].path,
+        "/matrix/_matrix/client/v3/presence/%40bob%2Fevil%3Fx%23y%3Aexample.org/status"
+    );
+}
+
+#[tokio::test]
+async fn events_mark_the_user_unavailable() {
+    let server = MockServer::start().await;
+    let sink = MatrixSink::new(server.url.clone(), "syt_token".to_string(), "@alice:example.org".to_string());
+
+    let start = Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap();
+    let end = Utc.with_ymd_and_hms(2026, 10, 18, 10, 0, 0).unwrap();
+    let update = StatusUpdate::for_event(status("In a meeting", ""), "Standup".to_string(), start, end, sent_at());
+    sink.set_status(&update).await.unwrap();
+
+    assert_eq!(
+        server.requests()[0].json(),
+        serde_json::json!({ "presence": "unavailable", "status_msg": "In a meeting" })
+    );
+}
+
+#[tokio::test]
+async fn reports_http_errors() {
+    let server = MockServer::start().await;
+    server.respond(
+        "PUT",
+        "/_matrix/client/v3/presence/%40alice%3Aexample.org/status",
+        401,
+        serde_json::json!({ "errcode": "M_UNKNOWN_TOKEN" }),
+    );
+    let sink = MatrixSink::new(server.url.clone(), "revoked".to_string(), "@alice:example.org".to_string());
+
+    assert!(sink.set_status(&StatusUpdate::idle(status("Around", ""), sent_at())).await.is_err());
+}
+
+#[tokio::test]
+async fn translates_shortcodes_to_unicode_emoji() {
+    let server = MockServer::start().await;
+    let sink = MatrixSink::new(server.url.clone(), "syt_token".to_string(), "@alice:example.org".to_string());
+
+    sink.set_status(&StatusUpdate::idle(status("Planning", ":calendar:"), sent_at())).await.unwrap();
+    sink.set_status(&StatusUpdate::idle(status("Planning", ":custom_emoji:"), sent_at())).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].json()["status_msg"], "📆 Planning");
+    // Unknown shortcodes are dropped rather than shown literally
+    assert_eq!(requests[1].json()["status_msg"], "Planning");
+}
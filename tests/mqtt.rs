@@ -0,0 +1,81 @@
+mod common;
+
+use calendar2discord::sink::{MqttSink, StatusSink};
+use calendar2discord::status::{Status, StatusUpdate};
+use chrono::{TimeZone, Utc};
+use common::MqttBroker;
+
+fn sink(broker: &MqttBroker) -> MqttSink {
+    MqttSink::new(
+        "127.0.0.1".to_string(),
+        broker.port,
+        false,
+        None,
+        None,
+        "calendar2discord".to_string(),
+        "42".to_string(),
+    )
+}
+
+fn update() -> StatusUpdate {
+    let mut update = StatusUpdate::idle(Status {
+        message: "Around".to_string(),
+        emoji: "💡".to_string(),
+    }, Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap());
+    update.next_transition = Some(Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap());
+    update
+}
+
+#[tokio::test]
+async fn publishes_retained_status_and_next_transition() {
+    let broker = MqttBroker::start().await;
+
+    sink(&broker).set_status(&update()).await.unwrap();
+
+    let published = broker.published();
+    assert_eq!(published.len(), 2);
+    assert!(published.iter().all(|message| message.retain));
+
+    assert_eq!(published[0].topic, "calendar2discord/42/status");
+    let status: serde_json::Value = serde_json::from_slice(&published[0].payload).unwrap();
+    assert_eq!(status["status"], serde_json::json!({ "message": "Around", "emoji": "💡" }));
+    assert_eq!(status["presence"], "available");
+    assert_eq!(status["sent_at"], "2026-10-18T08:00:00+00:00");
+
+    assert_eq!(published[1].topic, "calendar2discord/42/next_transition");
+    assert_eq!(published[1].payload, b"2026-10-18T09:00:00+00:00");
+}
+
+#[tokio::test]
+async fn overlapping_updates_connect_with_distinct_client_ids() {
+    let broker = MqttBroker::start().await;
+    let (loop_sink, refresh_sink) = (sink(&broker), sink(&broker));
+    let update = update();
+
+    let (first, second) = tokio::join!(loop_sink.set_status(&update), refresh_sink.set_status(&update));
+    first.unwrap();
+    second.unwrap();
+
+    let client_ids = broker.client_ids();
+    assert_eq!(client_ids.len(), 2);
+    assert_ne!(client_ids[0], client_ids[1]);
+    assert!(client_ids.iter().all(|id| id.starts_with("calendar2discord-42-")));
+    assert_eq!(broker.published().len(), 4);
+}
+
+#[tokio::test]
+async fn fails_when_the_broker_is_unreachable() {
+    // Bind and drop a listener to find a port nothing listens on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let unreachable = MqttSink::new(
+        "127.0.0.1".to_string(),
+        port,
+        false,
+        None,
+        None,
+        "calendar2discord".to_string(),
+        "42".to_string(),
+    );
+
+    assert!(unreachable.set_status(&update()).await.is_err());
+}
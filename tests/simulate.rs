@@ -0,0 +1,115 @@
+mod common;
+
+use calendar2discord::calendar::get_current_event;
+use calendar2discord::simulate::simulate;
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use common::{Sandbox, config, ics};
+
+const USER_ID: u64 = 7;
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 19, hour, minute, 0).unwrap()
+}
+
+fn sandbox() -> Sandbox {
+    Sandbox::new(config(USER_ID, &[("Standup", "In standup", "📅"), ("Review", "Reviewing", "👀")]))
+}
+
+fn summary(transition: &calendar2discord::simulate::Transition) -> (DateTime<Utc>, Option<&str>, &str) {
+    (transition.at, transition.event.as_deref(), transition.status.message.as_str())
+}
+
+#[test]
+fn back_to_back_events_hand_over_at_the_boundary() {
+    let sandbox = sandbox();
+    sandbox.write_calendar(
+        USER_ID,
+        "work.ics",
+        &ics(&[("Standup", at(9, 0), at(9, 30)), ("Review", at(9, 30), at(10, 0))]),
+    );
+
+    let simulation = simulate(USER_ID, at(8, 0), at(12, 0), at(8, 0));
+    let transitions: Vec<_> = simulation.transitions.iter().map(summary).collect();
+    assert_eq!(
+        transitions,
+        [
+            (at(8, 0), None, "Around"),
+            (at(9, 0), Some("Standup"), "In standup"),
+            (at(9, 30), Some("Review"), "Reviewing"),
+            (at(10, 0), None, "Around"),
+        ]
+    );
+    assert_eq!((simulation.recurring, simulation.zoned), (0, 0));
+}
+
+#[test]
+fn starting_inside_an_event_reports_it() {
+    let sandbox = sandbox();
+    sandbox.write_calendar(USER_ID, "work.ics", &ics(&[("Standup", at(9, 0), at(9, 30))]));
+
+    let simulation = simulate(USER_ID, at(9, 0), at(9, 45), at(9, 0));
+    let transitions: Vec<_> = simulation.transitions.iter().map(summary).collect();
+    assert_eq!(
+        transitions,
+        [(at(9, 0), Some("Standup"), "In standup"), (at(9, 30), None, "Around")]
+    );
+}
+
+#[test]
+fn every_transition_matches_what_the_status_loop_sees() {
+    let sandbox = sandbox();
+    sandbox.write_calendar(
+        USER_ID,
+        "work.ics",
+        &ics(&[
+            ("Standup", at(9, 0), at(9, 30)),
+            ("Review", at(9, 15), at(10, 0)),
+            ("Zero length", at(11, 0), at(11, 0)),
+            ("Review", at(13, 0), at(14, 0)),
+        ]),
+    );
+
+    let simulation = simulate(USER_ID, at(8, 0), at(15, 0), at(8, 0));
+    assert_eq!(simulation.transitions.len(), 6);
+    for (index, transition) in simulation.transitions.iter().enumerate() {
+        let until = simulation.transitions.get(index + 1).map_or(at(15, 0), |next| next.at);
+        // The loop's view just after the transition and just before the next one
+        for probe in [transition.at + TimeDelta::seconds(1), until - TimeDelta::seconds(1)] {
+            let current = get_current_event(USER_ID, probe);
+            assert_eq!(
+                current.as_ref().map(|event| event.summary()),
+                transition.event.as_deref(),
+                "at {probe}"
+            );
+        }
+    }
+}
+
+#[test]
+fn recurring_and_zoned_events_are_counted() {
+    let sandbox = sandbox();
+    sandbox.write_calendar(
+        USER_ID,
+        "work.ics",
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//calendar2discord//tests//EN\r\n\
+         BEGIN:VEVENT\r\nUID:standup@tests\r\nDTSTAMP:20260101T000000Z\r\nSUMMARY:Standup\r\n\
+         DTSTART:20261012T090000Z\r\nDTEND:20261012T093000Z\r\nRRULE:FREQ=DAILY\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:review@tests\r\nDTSTAMP:20260101T000000Z\r\nSUMMARY:Review\r\n\
+         DTSTART;TZID=Europe/Berlin:20261019T130000\r\nDTEND;TZID=Europe/Berlin:20261019T140000\r\nEND:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+    );
+
+    let simulation = simulate(USER_ID, at(8, 0), at(15, 0), at(8, 0));
+    assert_eq!(simulation.recurring, 1);
+    assert_eq!(simulation.zoned, 1);
+    // Neither a repeat of the series nor a converted time, just the Berlin wall clock read as UTC
+    let transitions: Vec<_> = simulation.transitions.iter().map(summary).collect();
+    assert_eq!(
+        transitions,
+        [
+            (at(8, 0), None, "Around"),
+            (at(13, 0), Some("Review"), "Reviewing"),
+            (at(14, 0), None, "Around"),
+        ]
+    );
+}
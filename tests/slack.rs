@@ -0,0 +1,73 @@
+mod common;
+
+use calendar2discord::sink::{SlackSink, StatusSink};
+use calendar2discord::status::{Status, StatusUpdate};
+use chrono::{DateTime, TimeZone, Utc};
+use common::MockServer;
+
+fn status(message: &str, emoji: &str) -> Status {
+    Status {
+        message: message.to_string(),
+        emoji: emoji.to_string(),
+    }
+}
+
+/// When the status loop resolved the updates sent below
+fn sent_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap()
+}
+
+#[tokio::test]
+async fn sets_the_profile_status_with_a_shortcode_and_expiry() {
+    let server = MockServer::start().await;
+    server.respond("POST", "/users.profile.set", 200, serde_json::json!({ "ok": true }));
+    let sink = SlackSink::new("xoxp-test".to_string(), server.url.clone());
+
+    let start = Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap();
+    let end = Utc.with_ymd_and_hms(2026, 10, 18, 10, 0, 0).unwrap();
+    let update = StatusUpdate::for_event(status("In a meeting", "📅"), "Standup".to_string(), start, end, sent_at());
+    sink.set_status(&update).await.unwrap();
+
+    let requests = server.requests_with("POST");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/users.profile.set");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer xoxp-test"));
+    assert_eq!(
+        requests[0].json(),
+        serde_json::json!({
+            "profile": {
+                "status_text": "In a meeting",
+                "status_emoji": ":date:",
+                "status_expiration": end.timestamp()
+            }
+        })
+    );
+}
+
+#[tokio::test]
+async fn unknown_emoji_and_idle_statuses_clear_emoji_and_expiry() {
+    let server = MockServer::start().await;
+    server.respond("POST", "/users.profile.set", 200, serde_json::json!({ "ok": true }));
+    let sink = SlackSink::new("xoxp-test".to_string(), server.url.clone());
+
+    sink.set_status(&StatusUpdate::idle(status("Around", "🦑"), sent_at())).await.unwrap();
+
+    let profile = &server.requests_with("POST")[0].json()["profile"];
+    assert_eq!(profile["status_emoji"], "");
+    assert_eq!(profile["status_expiration"], 0);
+}
+
+#[tokio::test]
+async fn reports_slack_errors_sent_with_a_200() {
+    let server = MockServer::start().await;
+    server.respond(
+        "POST",
+        "/users.profile.set",
+        200,
+        serde_json::json!({ "ok": false, "error": "invalid_auth" }),
+    );
+    let sink = SlackSink::new("revoked".to_string(), server.url.clone());
+
+    let error = sink.set_status(&StatusUpdate::idle(status("Around", "💡"), sent_at())).await.unwrap_err();
+    assert!(error.to_string().contains("invalid_auth"), "{error}");
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use calendar2discord::clock::{Clock, FixedClock};
+use calendar2discord::connection::tick;
+use calendar2discord::report::{Resolved, status_report};
+use calendar2discord::sink::{MemorySink, Sinks};
+use calendar2discord::state::load_state;
+use calendar2discord::status::{ObservedStatus, Status};
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use common::{Sandbox, config, ics};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+const USER_ID: u64 = 1234;
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 20, hour, minute, 0).unwrap()
+}
+
+#[tokio::test]
+async fn a_simulated_day_follows_the_calendar() {
+    let sandbox = Sandbox::new(config(USER_ID, &[("Meeting", "In a meeting", "📞"), ("Lunch", "At lunch", "🍔")]));
+    sandbox.write_calendar(
+        USER_ID,
+        "day.ics",
+        &ics(&[("Team Meeting", at(9, 0), at(10, 0)), ("Lunch", at(12, 0), at(12, 45))]),
+    );
+
+    let memory = MemorySink::new();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(0, 0));
+    let last_status_was_default = AtomicBool::new(false);
+
+    let mut ticks = 0;
+    while clock.now() < at(23, 59) {
+        let sleep = tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+        clock.advance(TimeDelta::from_std(sleep).unwrap());
+        ticks += 1;
+    }
+    // Idle ticks come once a minute, while an event sleeps straight through to its end
+    assert!(ticks < 24 * 60, "{ticks} ticks");
+
+    let updates = memory.updates();
+    let sequence: Vec<_> = updates
+        .iter()
+        .map(|update| (update.status.message.as_str(), update.event.as_deref()))
+        .collect();
+    assert_eq!(
+        sequence,
+        [
+            ("Around", None),
+            ("In a meeting", Some("Team Meeting")),
+            ("Around", None),
+            ("At lunch", Some("Lunch")),
+            ("Around", None),
+        ]
+    );
+
+    // Event statuses carry the event's times, and idle ones point at what comes next
+    assert_eq!((updates[1].starts_at, updates[1].ends_at), (Some(at(9, 0)), Some(at(10, 0))));
+    assert_eq!((updates[3].starts_at, updates[3].ends_at), (Some(at(12, 0)), Some(at(12, 45))));
+    assert_eq!(updates[0].next_transition, Some(at(9, 0)));
+    assert_eq!(updates[2].next_transition, Some(at(12, 0)));
+    assert_eq!(updates[4].next_transition, None);
+}
+
+#[tokio::test]
+async fn an_override_outranks_the_calendar_until_it_expires() {
+    let sandbox = Sandbox::new(config(USER_ID, &[("Meeting", "In a meeting", "📞")]));
+    sandbox.write_calendar(USER_ID, "day.ics", &ics(&[("Meeting", at(9, 0), at(11, 0))]));
+    std::fs::write(
+        sandbox.dir.join("state.json"),
+        serde_json::json!({
+            "status_override": {
+                "status": { "message": "Heads down", "emoji": "🎧" },
+                "until": at(10, 0)
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let memory = MemorySink::new();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(9, 30));
+    let last_status_was_default = AtomicBool::new(false);
+
+    while clock.now() < at(12, 0) {
+        let sleep = tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+        clock.advance(TimeDelta::from_std(sleep).unwrap());
+    }
+
+    let sequence: Vec<_> = memory
+        .updates()
+        .iter()
+        .map(|update| update.status.message.clone())
+        .collect();
+    assert_eq!(sequence, ["Heads down", "In a meeting", "Around"]);
+}
+
+/// Tick from the clock's time until `end`, sleeping as long as each tick asks
+async fn run_until(sinks: &Sinks, clock: &FixedClock, last_status_was_default: &AtomicBool, end: DateTime<Utc>) {
+    while clock.now() < end {
+        let sleep = tick(USER_ID, last_status_was_default, sinks, clock).await;
+        clock.advance(TimeDelta::from_std(sleep).unwrap());
+    }
+}
+
+fn focus() -> Status {
+    Status {
+        message: "Focus".to_string(),
+        emoji: "🎧".to_string(),
+    }
+}
+
+fn policy_config(policy: &str) -> serde_json::Value {
+    let mut config = config(USER_ID, &[("Meeting", "In a meeting", "📞"), ("Lunch", "At lunch", "🍔")]);
+    config["manual_status_policy"] = policy.into();
+    config
+}
+
+/// Set the default at 08:00, change the status by hand at 08:30, and list what gets pushed by 14:00
+async fn day_with_manual_change(policy: &str) -> (Sandbox, Vec<String>) {
+    let sandbox = Sandbox::new(policy_config(policy));
+    sandbox.write_calendar(
+        USER_ID,
+        "day.ics",
+        &ics(&[("Meeting", at(9, 0), at(10, 0)), ("Lunch", at(12, 0), at(13, 0))]),
+    );
+
+    let memory = MemorySink::observable();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(8, 0));
+    let last_status_was_default = AtomicBool::new(false);
+
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    clock.set(at(8, 30));
+    memory.set_by_hand(ObservedStatus {
+        status: focus(),
+        expires_at: None,
+    });
+    run_until(&sinks, &clock, &last_status_was_default, at(14, 0)).await;
+
+    let pushed = memory.updates().into_iter().map(|update| update.status.message).collect();
+    (sandbox, pushed)
+}
+
+#[tokio::test]
+async fn always_override_ignores_manual_changes() {
+    let (_sandbox, pushed) = day_with_manual_change("always_override").await;
+    // Events still take over, and the hand-set status is what gets restored after each of them
+    assert_eq!(pushed, ["Around", "In a meeting", "Focus", "At lunch", "Focus"]);
+    assert!(load_state().manual_hold.is_none());
+}
+
+#[tokio::test]
+async fn respect_manual_leaves_a_manual_status_alone() {
+    let (_sandbox, pushed) = day_with_manual_change("respect_manual").await;
+    assert_eq!(pushed, ["Around"]);
+
+    let hold = load_state().manual_hold.expect("manual change is held");
+    assert_eq!(hold.status, focus());
+    assert_eq!(hold.event.as_deref(), Some("Meeting"));
+    assert!(matches!(status_report(USER_ID, at(14, 0)).resolved, Resolved::Manual(_)));
+}
+
+#[tokio::test]
+async fn respect_until_next_event_resumes_when_the_event_changes() {
+    let (_sandbox, pushed) = day_with_manual_change("respect_until_next_event").await;
+    // Held through the meeting it was noticed in, then back to automatic updates once it ended
+    assert_eq!(pushed, ["Around", "Around", "At lunch", "Around"]);
+    assert!(load_state().manual_hold.is_none());
+}
+
+#[tokio::test]
+async fn manual_statuses_that_expire_are_not_held() {
+    let sandbox = Sandbox::new(policy_config("respect_manual"));
+    sandbox.write_calendar(USER_ID, "day.ics", &ics(&[("Meeting", at(9, 0), at(10, 0))]));
+
+    let memory = MemorySink::observable();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(8, 0));
+    let last_status_was_default = AtomicBool::new(false);
+
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    memory.set_by_hand(ObservedStatus {
+        status: focus(),
+        expires_at: Some(at(8, 45)),
+    });
+    clock.set(at(8, 30));
+    run_until(&sinks, &clock, &last_status_was_default, at(11, 0)).await;
+
+    let pushed: Vec<_> = memory.updates().into_iter().map(|update| update.status.message).collect();
+    assert_eq!(pushed, ["Around", "In a meeting", "Around"]);
+}
+
+fn write_hold(sandbox: &Sandbox, until: Option<DateTime<Utc>>) {
+    let hold = serde_json::json!({ "status": focus(), "until": until });
+    std::fs::write(
+        sandbox.dir.join("state.json"),
+        serde_json::json!({ "manual_hold": hold }).to_string(),
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn always_override_clears_a_hold_left_by_another_policy() {
+    let sandbox = Sandbox::new(policy_config("always_override"));
+    sandbox.write_calendar(USER_ID, "day.ics", &ics(&[("Meeting", at(9, 0), at(10, 0))]));
+    write_hold(&sandbox, None);
+
+    assert!(!matches!(status_report(USER_ID, at(9, 30)).resolved, Resolved::Manual(_)));
+
+    let memory = MemorySink::observable();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(9, 30));
+    tick(USER_ID, &AtomicBool::new(false), &sinks, &clock).await;
+
+    assert_eq!(memory.updates()[0].status.message, "In a meeting");
+    assert!(load_state().manual_hold.is_none());
+}
+
+#[tokio::test]
+async fn reports_ignore_expired_holds() {
+    let sandbox = Sandbox::new(policy_config("respect_manual"));
+    write_hold(&sandbox, Some(at(9, 0)));
+
+    assert!(matches!(status_report(USER_ID, at(8, 59)).resolved, Resolved::Manual(_)));
+    assert!(matches!(status_report(USER_ID, at(9, 0)).resolved, Resolved::Default(_)));
+}
+
+/// Push the default at 08:00, set `manual` by hand at 08:30, then run through a 09:00-10:00 meeting
+async fn meeting_after_manual_change(manual: ObservedStatus) -> Vec<calendar2discord::status::StatusUpdate> {
+    let memory = MemorySink::observable();
+    let sinks: Sinks = Arc::new(vec![Box::new(memory.clone())]);
+    let clock = FixedClock::new(at(8, 0));
+    let last_status_was_default = AtomicBool::new(false);
+
+    tick(USER_ID, &last_status_was_default, &sinks, &clock).await;
+    clock.set(at(8, 30));
+    memory.set_by_hand(manual);
+    run_until(&sinks, &clock, &last_status_was_default, at(12, 0)).await;
+    memory.updates()
+}
+
+#[tokio::test]
+async fn the_status_from_before_an_event_is_restored_with_its_expiry() {
+    let sandbox = Sandbox::new(policy_config("always_override"));
+    sandbox.write_calendar(USER_ID, "day.ics", &ics(&[("Meeting", at(9, 0), at(10, 0))]));
+
+    let updates = meeting_after_manual_change(ObservedStatus {
+        status: focus(),
+        expires_at: Some(at(11, 0)),
+    })
+    .await;
+
+    let pushed: Vec<_> = updates.iter().map(|update| update.status.message.as_str()).collect();
+    assert_eq!(pushed, ["Around", "In a meeting", "Focus"]);
+    assert_eq!(updates[2].status, focus());
+    assert_eq!(updates[2].expires_at, Some(at(11, 0)));
+    assert!(load_state().previous_status.is_none());
+}
+
+#[tokio::test]
+async fn a_status_that_expired_during_the_event_falls_back_to_the_default() {
+    let sandbox = Sandbox::new(policy_config("always_override"));
+    sandbox.write_calendar(USER_ID, "day.ics", &ics(&[("Meeting", at(9, 0), at(10, 0))]));
+
+    let updates = meeting_after_manual_change(ObservedStatus {
+        status: focus(),
+        expires_at: Some(at(9, 30)),
+    })
+    .await;
+
+    let pushed: Vec<_> = updates.iter().map(|update| update.status.message.as_str()).collect();
+    assert_eq!(pushed, ["Around", "In a meeting", "Around"]);
+    assert_eq!(updates[2].expires_at, None);
+}
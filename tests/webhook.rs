@@ -0,0 +1,83 @@
+mod common;
+
+use calendar2discord::sink::webhook::SIGNATURE_HEADER;
+use calendar2discord::sink::{StatusSink, WebhookSink};
+use calendar2discord::status::{Status, StatusUpdate};
+use chrono::{TimeZone, Utc};
+use common::MockServer;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+fn update() -> StatusUpdate {
+    StatusUpdate::idle(Status {
+        message: "Around".to_string(),
+        emoji: "💡".to_string(),
+    }, Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap())
+}
+
+fn hook(server: &MockServer) -> String {
+    format!("{}/hook", server.url)
+}
+
+#[tokio::test]
+async fn signs_the_exact_body_with_the_secret() {
+    let server = MockServer::start().await;
+    let sink = WebhookSink::new(vec![hook(&server)], Some("s3cret".to_string()), 0);
+
+    sink.set_status(&update()).await.unwrap();
+
+    let requests = server.requests_with("POST");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/hook");
+    assert_eq!(requests[0].json()["status"]["message"], "Around");
+    assert_eq!(requests[0].json()["sent_at"], "2026-10-18T08:00:00+00:00");
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+    mac.update(requests[0].body.as_bytes());
+    let expected: String = mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect();
+    assert_eq!(requests[0].header(SIGNATURE_HEADER), Some(format!("sha256={expected}").as_str()));
+}
+
+#[tokio::test]
+async fn sends_no_signature_without_a_secret() {
+    let server = MockServer::start().await;
+    let sink = WebhookSink::new(vec![hook(&server)], None, 0);
+
+    sink.set_status(&update()).await.unwrap();
+
+    let requests = server.requests_with("POST");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].header(SIGNATURE_HEADER), None);
+}
+
+#[tokio::test]
+async fn retries_server_errors_up_to_the_limit() {
+    let server = MockServer::start().await;
+    server.respond("POST", "/hook", 503, serde_json::json!({}));
+    let sink = WebhookSink::new(vec![hook(&server)], None, 2);
+
+    let error = sink.set_status(&update()).await.unwrap_err();
+
+    assert!(error.to_string().contains("Giving up after 3 attempts"), "{error}");
+    assert_eq!(server.requests_with("POST").len(), 3);
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+    let server = MockServer::start().await;
+    server.respond("POST", "/hook", 404, serde_json::json!({}));
+    let sink = WebhookSink::new(vec![hook(&server)], None, 3);
+
+    assert!(sink.set_status(&update()).await.is_err());
+    assert_eq!(server.requests_with("POST").len(), 1);
+}
+
+#[tokio::test]
+async fn one_failing_url_does_not_stop_the_others() {
+    let (failing, working) = (MockServer::start().await, MockServer::start().await);
+    failing.respond("POST", "/hook", 400, serde_json::json!({}));
+    let sink = WebhookSink::new(vec![hook(&failing), hook(&working)], None, 0);
+
+    assert!(sink.set_status(&update()).await.is_err());
+    assert_eq!(working.requests_with("POST").len(), 1);
+}